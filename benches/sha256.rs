@@ -0,0 +1,133 @@
+//! Build, witness generation, proving and verification times of the SHA-256
+//! circuits, per config preset and message size, and build and proving times
+//! per `Sha256Backend`.
+//!
+//! Benchmarks are grouped as `<fixed|variable>/<preset>/<phase>/<blocks>` and
+//! `backend/<backend>/<phase>/<blocks>`, so one shape, preset or backend can
+//! be run on its own, e.g. `cargo bench --bench sha256 -- fixed/RecursionFriendly`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use plonky2::iop::generator::generate_partial_witness;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData};
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2_sha256::circuit::{
+    array_to_bits, fill_variable_length_circuits, make_circuits, make_circuits_u32_with_backend,
+    make_variable_length_circuits, Sha256Backend, EXAMPLE_MESSAGE,
+};
+use plonky2_sha256::config::Sha256ConfigPreset;
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+/// Padded message sizes; 46 blocks is the whole of `EXAMPLE_MESSAGE`.
+const BLOCK_COUNTS: [usize; 4] = [1, 2, 8, 46];
+
+/// Padded message sizes for comparing backends.
+const BACKEND_BLOCK_COUNTS: [usize; 2] = [1, 8];
+
+/// The longest prefix of `EXAMPLE_MESSAGE` that pads to `blocks` blocks.
+fn message(blocks: usize) -> &'static [u8] {
+    let len = (64 * blocks - 9).min(EXAMPLE_MESSAGE.len());
+    assert_eq!(
+        (len + 9).div_ceil(64),
+        blocks,
+        "EXAMPLE_MESSAGE is too short for {blocks} blocks"
+    );
+    &EXAMPLE_MESSAGE[..len]
+}
+
+/// Builds the fixed- or variable-length circuit for `msg` under `preset`,
+/// along with a witness for `msg`.
+fn build_circuit(
+    preset: Sha256ConfigPreset,
+    variable_length: bool,
+    msg: &[u8],
+) -> (CircuitData<F, C, D>, PartialWitness<F>) {
+    let mut builder = CircuitBuilder::<F, D>::new(preset.config());
+    let mut pw = PartialWitness::new();
+    if variable_length {
+        let max_total_bits = (msg.len() * 8 + 65).div_ceil(512) * 512;
+        let targets = make_variable_length_circuits(&mut builder, max_total_bits);
+        fill_variable_length_circuits::<F, D>(&mut pw, msg, max_total_bits, &targets).unwrap();
+    } else {
+        let targets = make_circuits(&mut builder, (msg.len() * 8) as u64);
+        for (target, bit) in targets.message.iter().zip(array_to_bits(msg)) {
+            pw.set_bool_target(*target, bit).unwrap();
+        }
+    }
+    (builder.build::<C>(), pw)
+}
+
+fn bench_sha256(c: &mut Criterion) {
+    for variable_length in [false, true] {
+        let shape = if variable_length { "variable" } else { "fixed" };
+        for preset in Sha256ConfigPreset::ALL {
+            let mut group = c.benchmark_group(format!("{shape}/{preset:?}"));
+            // Even one block takes long enough that the default 100 samples
+            // would not finish in reasonable time.
+            group.sample_size(10);
+            for blocks in BLOCK_COUNTS {
+                let msg = message(blocks);
+                let (data, pw) = build_circuit(preset, variable_length, msg);
+                let proof = data.prove(pw.clone()).unwrap();
+
+                group.bench_function(BenchmarkId::new("build", blocks), |b| {
+                    b.iter(|| build_circuit(preset, variable_length, msg))
+                });
+                group.bench_function(BenchmarkId::new("witness", blocks), |b| {
+                    b.iter(|| {
+                        generate_partial_witness(pw.clone(), &data.prover_only, &data.common)
+                            .unwrap()
+                    })
+                });
+                group.bench_function(BenchmarkId::new("prove", blocks), |b| {
+                    b.iter(|| data.prove(pw.clone()).unwrap())
+                });
+                group.bench_function(BenchmarkId::new("verify", blocks), |b| {
+                    b.iter(|| data.verify(proof.clone()).unwrap())
+                });
+            }
+            group.finish();
+        }
+    }
+}
+
+/// Builds the fixed-length circuit for `msg` with the round functions of
+/// `backend`, along with a witness for `msg`.
+fn build_backend_circuit(
+    backend: Sha256Backend,
+    msg: &[u8],
+) -> (CircuitData<F, C, D>, PartialWitness<F>) {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let targets = make_circuits_u32_with_backend(&mut builder, (msg.len() * 8) as u64, backend);
+    let mut pw = PartialWitness::new();
+    for (target, bit) in targets.message.iter().zip(array_to_bits(msg)) {
+        pw.set_bool_target(*target, bit).unwrap();
+    }
+    (builder.build::<C>(), pw)
+}
+
+fn bench_backends(c: &mut Criterion) {
+    for backend in Sha256Backend::ALL {
+        let mut group = c.benchmark_group(format!("backend/{backend:?}"));
+        group.sample_size(10);
+        for blocks in BACKEND_BLOCK_COUNTS {
+            let msg = message(blocks);
+            let (data, pw) = build_backend_circuit(backend, msg);
+
+            group.bench_function(BenchmarkId::new("build", blocks), |b| {
+                b.iter(|| build_backend_circuit(backend, msg))
+            });
+            group.bench_function(BenchmarkId::new("prove", blocks), |b| {
+                b.iter(|| data.prove(pw.clone()).unwrap())
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_sha256, bench_backends);
+criterion_main!(benches);
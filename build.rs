@@ -0,0 +1,55 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Hashes every source file under `src`, and the `Cargo.lock` pinning the
+/// `plonky2` and `plonky2_u32` revisions, into `SHA256_CIRCUIT_SOURCE_HASH`, so
+/// the circuit cache tells builds of different sources or dependencies apart.
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+
+    let mut files = Vec::new();
+    collect_rust_files(Path::new("src"), &mut files);
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for file in &files {
+        let contents = fs::read(file).unwrap_or_else(|e| panic!("reading {}: {e}", file.display()));
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+
+    // The lock file sits next to the manifest, or at the workspace root when
+    // the crate is a workspace member.
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    if let Some(lock) = manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|lock| lock.is_file())
+    {
+        println!("cargo:rerun-if-changed={}", lock.display());
+        let contents =
+            fs::read(&lock).unwrap_or_else(|e| panic!("reading {}: {e}", lock.display()));
+        hasher.update(b"Cargo.lock");
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    let hash: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    println!("cargo:rustc-env=SHA256_CIRCUIT_SOURCE_HASH={hash}");
+}
+
+fn collect_rust_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap_or_else(|e| panic!("reading {}: {e}", dir.display())) {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_rust_files(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::{anyhow, ensure, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::target::Target,
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitConfig, CircuitData},
+        config::{AlgebraicHasher, GenericConfig},
+    },
+    util::serialization::{Buffer, Read, Write},
+};
+use plonky2_u32::gadgets::arithmetic_u32::U32Target;
+use sha2::{Digest, Sha256};
+
+use crate::circuit::{
+    make_public_circuits, make_variable_length_circuits_u32, PublicMessage, Sha256U32Targets,
+    VariableLengthSha256U32Targets,
+};
+use crate::serialization::{Sha256GateSerializer, Sha256GeneratorSerializer};
+
+const MAGIC: &[u8; 8] = b"PSHA256C";
+/// Bumped whenever the file layout changes. Circuit changes are caught by the
+/// source hash in the fingerprint.
+const FORMAT_VERSION: u32 = 1;
+
+/// Which SHA-256 circuit to build.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Sha256CircuitShape {
+    /// `make_public_circuits` for a fixed message length.
+    FixedLength {
+        msg_len_in_bits: u64,
+        public_message: PublicMessage,
+    },
+    /// `make_variable_length_circuits_u32` for padded messages of up to
+    /// `max_total_bits` bits.
+    VariableLength { max_total_bits: usize },
+}
+
+pub enum Sha256CircuitTargets {
+    FixedLength(Sha256U32Targets),
+    VariableLength(VariableLengthSha256U32Targets),
+}
+
+pub struct CachedSha256Circuit<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub data: CircuitData<F, C, D>,
+    pub targets: Sha256CircuitTargets,
+}
+
+/// Directory of built SHA-256 circuits, one file per circuit. A file holds
+/// `MAGIC`, the fingerprint of the circuit, a SHA-256 checksum of the payload
+/// and the payload: the targets, then the serialized `CircuitData`.
+///
+/// The fingerprint covers the shape, the `CircuitConfig`, the `GenericConfig`
+/// and a hash of the crate sources and `Cargo.lock` computed by `build.rs`, so
+/// a file written by another build, or against other dependency revisions, is
+/// never used.
+pub struct Sha256CircuitCache {
+    dir: PathBuf,
+}
+
+impl Sha256CircuitCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn fingerprint<F, C, const D: usize>(
+        config: &CircuitConfig,
+        shape: &Sha256CircuitShape,
+    ) -> [u8; 32]
+    where
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+    {
+        let key = format!(
+            "{} {} {} {} {:?} {:?}",
+            env!("SHA256_CIRCUIT_SOURCE_HASH"),
+            FORMAT_VERSION,
+            std::any::type_name::<C>(),
+            D,
+            config,
+            shape
+        );
+        Sha256::digest(key.as_bytes()).into()
+    }
+
+    pub fn path(&self, fingerprint: &[u8; 32]) -> PathBuf {
+        let name: String = fingerprint.iter().map(|b| format!("{b:02x}")).collect();
+        self.dir.join(format!("sha256-{name}.bin"))
+    }
+
+    /// Loads the circuit from the cache, or builds and stores it when it is
+    /// missing or fails the integrity checks.
+    pub fn load_or_build<F, C, const D: usize>(
+        &self,
+        config: &CircuitConfig,
+        shape: Sha256CircuitShape,
+    ) -> Result<CachedSha256Circuit<F, C, D>>
+    where
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F> + 'static,
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        match self.load(config, shape) {
+            Ok(Some(circuit)) => return Ok(circuit),
+            Ok(None) => {}
+            Err(e) => log::warn!("rebuilding cached SHA-256 circuit: {e:#}"),
+        }
+        let circuit = build(config, shape);
+        self.store(config, shape, &circuit)?;
+        Ok(circuit)
+    }
+
+    /// Returns `Ok(None)` if the circuit is not cached, and an error if the
+    /// cached file is stale or corrupted.
+    pub fn load<F, C, const D: usize>(
+        &self,
+        config: &CircuitConfig,
+        shape: Sha256CircuitShape,
+    ) -> Result<Option<CachedSha256Circuit<F, C, D>>>
+    where
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F> + 'static,
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let fingerprint = Self::fingerprint::<F, C, D>(config, &shape);
+        let path = self.path(&fingerprint);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+
+        ensure!(
+            bytes.len() >= MAGIC.len() + 64 && bytes.starts_with(MAGIC),
+            "{} is not a cached circuit",
+            path.display()
+        );
+        let (stored_fingerprint, rest) = bytes[MAGIC.len()..].split_at(32);
+        let (checksum, payload) = rest.split_at(32);
+        ensure!(
+            stored_fingerprint == fingerprint,
+            "{} was built for a different circuit",
+            path.display()
+        );
+        ensure!(
+            Sha256::digest(payload).as_slice() == checksum,
+            "{} is corrupted",
+            path.display()
+        );
+
+        let mut buffer = Buffer::new(payload);
+        let targets = read_targets(&mut buffer, &shape)
+            .map_err(|_| anyhow!("failed to read targets from {}", path.display()))?;
+        let data = CircuitData::from_bytes(
+            buffer.unread_bytes(),
+            &Sha256GateSerializer,
+            &Sha256GeneratorSerializer::<C, D>::default(),
+        )
+        .map_err(|_| anyhow!("failed to read circuit data from {}", path.display()))?;
+        ensure!(
+            data.common.config == *config,
+            "{} was built with a different config",
+            path.display()
+        );
+
+        Ok(Some(CachedSha256Circuit { data, targets }))
+    }
+
+    pub fn store<F, C, const D: usize>(
+        &self,
+        config: &CircuitConfig,
+        shape: Sha256CircuitShape,
+        circuit: &CachedSha256Circuit<F, C, D>,
+    ) -> Result<()>
+    where
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F> + 'static,
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let mut payload = Vec::new();
+        write_targets(&mut payload, &circuit.targets)
+            .map_err(|_| anyhow!("failed to serialize targets"))?;
+        let data = circuit
+            .data
+            .to_bytes(
+                &Sha256GateSerializer,
+                &Sha256GeneratorSerializer::<C, D>::default(),
+            )
+            .map_err(|_| anyhow!("failed to serialize circuit data"))?;
+        payload.extend(data);
+
+        let fingerprint = Self::fingerprint::<F, C, D>(config, &shape);
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(fingerprint);
+        bytes.extend(Sha256::digest(&payload));
+        bytes.extend(payload);
+
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("creating {}", self.dir.display()))?;
+        let path = self.path(&fingerprint);
+        write_atomically(&path, &bytes)
+    }
+}
+
+fn build<F, C, const D: usize>(
+    config: &CircuitConfig,
+    shape: Sha256CircuitShape,
+) -> CachedSha256Circuit<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+    let targets = match shape {
+        Sha256CircuitShape::FixedLength {
+            msg_len_in_bits,
+            public_message,
+        } => Sha256CircuitTargets::FixedLength(make_public_circuits(
+            &mut builder,
+            msg_len_in_bits,
+            public_message,
+        )),
+        Sha256CircuitShape::VariableLength { max_total_bits } => {
+            Sha256CircuitTargets::VariableLength(make_variable_length_circuits_u32(
+                &mut builder,
+                max_total_bits,
+            ))
+        }
+    };
+    let data = builder.build::<C>();
+    CachedSha256Circuit { data, targets }
+}
+
+/// Writes to a temporary file first so a crash never leaves a truncated file
+/// under the final name.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+    fs::write(&tmp, bytes).with_context(|| format!("writing {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("renaming to {}", path.display()))
+}
+
+fn write_targets(
+    dst: &mut Vec<u8>,
+    targets: &Sha256CircuitTargets,
+) -> plonky2::util::serialization::IoResult<()> {
+    match targets {
+        Sha256CircuitTargets::FixedLength(targets) => {
+            dst.write_target_bool_vec(&targets.message)?;
+            dst.write_target_vec(&targets.digest.map(|w| w.0))
+        }
+        Sha256CircuitTargets::VariableLength(targets) => {
+            dst.write_target_bool_vec(&targets.message)?;
+            dst.write_target_vec(&targets.digest.map(|w| w.0))?;
+            dst.write_target(targets.msg_len.0)?;
+            dst.write_target(targets.msg_blocks.0)
+        }
+    }
+}
+
+fn read_targets(
+    src: &mut Buffer,
+    shape: &Sha256CircuitShape,
+) -> plonky2::util::serialization::IoResult<Sha256CircuitTargets> {
+    let message = src.read_target_bool_vec()?;
+    let digest: Vec<Target> = src.read_target_vec()?;
+    let digest = core::array::from_fn(|i| U32Target(digest[i]));
+    Ok(match shape {
+        Sha256CircuitShape::FixedLength { .. } => {
+            Sha256CircuitTargets::FixedLength(Sha256U32Targets { message, digest })
+        }
+        Sha256CircuitShape::VariableLength { .. } => {
+            let msg_len = U32Target(src.read_target()?);
+            let msg_blocks = U32Target(src.read_target()?);
+            Sha256CircuitTargets::VariableLength(VariableLengthSha256U32Targets {
+                message,
+                digest,
+                msg_len,
+                msg_blocks,
+            })
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+    use sha2::Digest;
+
+    use crate::cache::{Sha256CircuitCache, Sha256CircuitShape, Sha256CircuitTargets};
+    use crate::circuit::{array_to_bits, digest_from_proof, PublicMessage, EXAMPLE_MESSAGE};
+
+    #[test]
+    fn test_circuit_cache() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let dir = std::env::temp_dir().join(format!("sha256-cache-test-{}", std::process::id()));
+        let cache = Sha256CircuitCache::new(&dir);
+        let config = CircuitConfig::standard_recursion_config();
+        let msg = &EXAMPLE_MESSAGE[..100];
+        let shape = Sha256CircuitShape::FixedLength {
+            msg_len_in_bits: (msg.len() * 8) as u64,
+            public_message: PublicMessage::None,
+        };
+
+        assert!(cache.load::<F, C, D>(&config, shape)?.is_none());
+        let built = cache.load_or_build::<F, C, D>(&config, shape)?;
+        let loaded = cache.load::<F, C, D>(&config, shape)?.unwrap();
+        assert_eq!(loaded.data.verifier_only, built.data.verifier_only);
+
+        let Sha256CircuitTargets::FixedLength(targets) = &loaded.targets else {
+            panic!("expected fixed-length targets");
+        };
+        let mut pw = PartialWitness::new();
+        for (target, bit) in targets.message.iter().zip(array_to_bits(msg)) {
+            pw.set_bool_target(*target, bit)?;
+        }
+        let proof = loaded.data.prove(pw)?;
+        assert_eq!(
+            digest_from_proof(&proof),
+            sha2::Sha256::digest(msg).as_slice()
+        );
+        built.data.verify(proof)?;
+
+        // A corrupted file is detected, then replaced by a rebuilt circuit.
+        let path = cache.path(&Sha256CircuitCache::fingerprint::<F, C, D>(&config, &shape));
+        let mut bytes = std::fs::read(&path)?;
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        std::fs::write(&path, bytes)?;
+        assert!(cache.load::<F, C, D>(&config, shape).is_err());
+        cache.load_or_build::<F, C, D>(&config, shape)?;
+        assert!(cache.load::<F, C, D>(&config, shape)?.is_some());
+
+        // Another config maps to another file.
+        let other = CircuitConfig::standard_ecc_config();
+        assert!(cache.load::<F, C, D>(&other, shape)?.is_none());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,21 +1,32 @@
 use anyhow::Result;
 use plonky2::{
     field::extension::Extendable,
-    hash::hash_types::RichField,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
     iop::{
-        target::BoolTarget,
+        target::{BoolTarget, Target},
         witness::{PartialWitness, WitnessWrite},
     },
-    plonk::circuit_builder::CircuitBuilder,
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        config::{GenericConfig, Hasher},
+        proof::ProofWithPublicInputs,
+    },
 };
 use plonky2_u32::{
     gadgets::arithmetic_u32::{CircuitBuilderU32, U32Target},
     witness::WitnessU32,
 };
-use std::cell::RefCell;
-use std::rc::Rc;
+use sha2::{Digest, Sha256};
+use std::ops::Range;
 
 use crate::gadgets::XorOps;
+use crate::gates::SigmaKind;
+use crate::lazy::LazyU32WithBits;
+use crate::lookup::LookupRoundFunctions;
+use crate::spread::SpreadRoundFunctions;
 
 pub const CHUNK_SIZE: usize = 32;
 
@@ -25,6 +36,12 @@ pub const H256: [u32; 8] = [
     0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19
 ];
 
+#[rustfmt::skip]
+pub const H224: [u32; 8] = [
+    0xc1059ed8, 0x367cd507, 0x3070dd17, 0xf70e5939,
+    0xffc00b31, 0x68581511, 0x64f98fa7, 0xbefa4fa4
+];
+
 /// Constants necessary for SHA-256 family of digests.
 #[rustfmt::skip]
 pub const K256: [u32; 64] = [
@@ -46,83 +63,6 @@ pub const K256: [u32; 64] = [
     0x90BEFFFA, 0xA4506CEB, 0xBEF9A3F7, 0xC67178F2
 ];
 
-/// Inner mutable state for lazy evaluation
-struct LazyU32WithBitsInner<F: RichField + Extendable<D>, const D: usize> {
-    u32_target: Option<U32Target>,
-    bits: Option<Vec<BoolTarget>>,
-    builder: *mut CircuitBuilder<F, D>,
-}
-
-/// Represents a U32 value with lazy bit decomposition
-pub struct LazyU32WithBits<F: RichField + Extendable<D>, const D: usize> {
-    inner: Rc<RefCell<LazyU32WithBitsInner<F, D>>>,
-}
-
-impl<F: RichField + Extendable<D>, const D: usize> Clone for LazyU32WithBits<F, D> {
-    fn clone(&self) -> Self {
-        Self {
-            inner: Rc::clone(&self.inner),
-        }
-    }
-}
-
-impl<F: RichField + Extendable<D>, const D: usize> LazyU32WithBits<F, D> {
-    /// Create from a U32Target (bits will be computed lazily)
-    pub fn from_u32(builder: &mut CircuitBuilder<F, D>, u32_target: U32Target) -> Self {
-        Self {
-            inner: Rc::new(RefCell::new(LazyU32WithBitsInner {
-                u32_target: Some(u32_target),
-                bits: None,
-                builder: builder as *mut _,
-            })),
-        }
-    }
-
-    /// Create from bits (u32 will be computed lazily)
-    pub fn from_bits(builder: &mut CircuitBuilder<F, D>, bits: Vec<BoolTarget>) -> Self {
-        assert_eq!(bits.len(), 32);
-        Self {
-            inner: Rc::new(RefCell::new(LazyU32WithBitsInner {
-                u32_target: None,
-                bits: Some(bits),
-                builder: builder as *mut _,
-            })),
-        }
-    }
-
-    /// Get the U32Target, computing it from bits if necessary
-    pub fn get_u32(&self) -> U32Target {
-        let mut inner = self.inner.borrow_mut();
-        match inner.u32_target {
-            Some(u32) => u32,
-            None => {
-                // Compute from bits
-                let builder = unsafe { &mut *inner.builder };
-                let bits = inner.bits.as_ref().unwrap();
-                let u32_target = bits_to_u32_target(builder, bits.clone());
-                inner.u32_target = Some(u32_target);
-                u32_target
-            }
-        }
-    }
-
-    /// Get the bits, computing them from U32 if necessary
-    pub fn get_bits(&self) -> Vec<BoolTarget> {
-        let mut inner = self.inner.borrow_mut();
-        match &inner.bits {
-            Some(bits) => bits.clone(),
-            None => {
-                // Compute from u32
-                let u32_target = inner.u32_target.unwrap();
-                let builder = unsafe { &mut *inner.builder };
-                let bits = u32_to_bits_target::<F, D, 2>(builder, &u32_target);
-                inner.bits = Some(bits.clone());
-                bits
-            }
-        }
-    }
-}
-
 pub fn array_to_bits(bytes: &[u8]) -> Vec<bool> {
     let len = bytes.len();
     let mut ret = Vec::new();
@@ -135,6 +75,35 @@ pub fn array_to_bits(bytes: &[u8]) -> Vec<bool> {
     ret
 }
 
+/// Allocates the bits of `len_in_bytes` bytes; fill them with `fill_bytes`.
+pub(crate) fn add_virtual_bytes<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    len_in_bytes: usize,
+) -> Vec<BoolTarget> {
+    (0..len_in_bytes * 8)
+        .map(|_| builder.add_virtual_bool_target_unsafe())
+        .collect()
+}
+
+/// Sets `targets` to the big-endian bits of `bytes`.
+pub(crate) fn fill_bytes<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    bytes: &[u8],
+    targets: &[BoolTarget],
+) -> Result<()> {
+    assert_eq!(
+        bytes.len() * 8,
+        targets.len(),
+        "expected {} bytes (got {})",
+        targets.len() / 8,
+        bytes.len()
+    );
+    for (target, bit) in targets.iter().zip(array_to_bits(bytes)) {
+        pw.set_bool_target(*target, bit)?;
+    }
+    Ok(())
+}
+
 pub fn u32_to_bits_target<F: RichField + Extendable<D>, const D: usize, const B: usize>(
     builder: &mut CircuitBuilder<F, D>,
     a: &U32Target,
@@ -156,100 +125,46 @@ pub fn bits_to_u32_target<F: RichField + Extendable<D>, const D: usize>(
     U32Target(builder.le_sum_optimized(bits_target[0..32].iter().rev()))
 }
 
-// define ROTATE(x, y)  (((x)>>(y)) | ((x)<<(32-(y))))
-fn rotate32(y: usize) -> Vec<usize> {
-    let mut res = Vec::new();
-    for i in 32 - y..32 {
-        res.push(i);
-    }
-    for i in 0..32 - y {
-        res.push(i);
-    }
-    res
-}
-
-// x>>y
-// Assume: 0 at index 32
-fn shift32(y: usize) -> Vec<usize> {
-    let mut res = Vec::new();
-    for _ in 32 - y..32 {
-        res.push(32);
-    }
-    for i in 0..32 - y {
-        res.push(i);
-    }
-    res
-}
-
-fn xor3_with_permutation<F: RichField + Extendable<D>, const D: usize>(
-    builder: &mut CircuitBuilder<F, D>,
-    a_bits: &Vec<BoolTarget>,
-    p1: &Vec<usize>,
-    p2: &Vec<usize>,
-    p3: &Vec<usize>,
-) -> Vec<BoolTarget> {
-    let mut res = Vec::new();
-    for i in 0..CHUNK_SIZE {
-        let a1 = a_bits[p1[i]];
-        let a2 = a_bits[p2[i]];
-        let a3 = a_bits[p3[i]];
-        res.push(builder.add_xor3(a1, a2, a3));
-    }
-    res
-}
+// Each sigma is a single `SigmaGate` op on the bits of its input.
 
 //#define Sigma0(x)    (ROTATE((x), 2) ^ ROTATE((x),13) ^ ROTATE((x),22))
 fn big_sigma0_lazy<F: RichField + Extendable<D>, const D: usize>(
     builder: &mut CircuitBuilder<F, D>,
-    a: &LazyU32WithBits<F, D>,
-) -> LazyU32WithBits<F, D> {
-    let a_bits = a.get_bits(); // Only decompose when needed
-    let rotate2 = rotate32(2);
-    let rotate13 = rotate32(13);
-    let rotate22 = rotate32(22);
-    let res_bits = xor3_with_permutation(builder, &a_bits, &rotate2, &rotate13, &rotate22);
-    LazyU32WithBits::from_bits(builder, res_bits)
+    a: &LazyU32WithBits,
+) -> LazyU32WithBits {
+    let a_bits = a.get_bits(builder); // Only decompose when needed
+    let res_bits = builder.add_sigma(SigmaKind::BigSigma0, &a_bits);
+    LazyU32WithBits::from_bits(res_bits)
 }
 
 //#define Sigma1(x)    (ROTATE((x), 6) ^ ROTATE((x),11) ^ ROTATE((x),25))
 fn big_sigma1_lazy<F: RichField + Extendable<D>, const D: usize>(
     builder: &mut CircuitBuilder<F, D>,
-    a: &LazyU32WithBits<F, D>,
-) -> LazyU32WithBits<F, D> {
-    let a_bits = a.get_bits();
-    let rotate6 = rotate32(6);
-    let rotate11 = rotate32(11);
-    let rotate25 = rotate32(25);
-    let res_bits = xor3_with_permutation(builder, &a_bits, &rotate6, &rotate11, &rotate25);
-    LazyU32WithBits::from_bits(builder, res_bits)
+    a: &LazyU32WithBits,
+) -> LazyU32WithBits {
+    let a_bits = a.get_bits(builder);
+    let res_bits = builder.add_sigma(SigmaKind::BigSigma1, &a_bits);
+    LazyU32WithBits::from_bits(res_bits)
 }
 
 //#define sigma0(x)    (ROTATE((x), 7) ^ ROTATE((x),18) ^ ((x)>> 3))
 fn sigma0_lazy<F: RichField + Extendable<D>, const D: usize>(
     builder: &mut CircuitBuilder<F, D>,
-    a: &LazyU32WithBits<F, D>,
-) -> LazyU32WithBits<F, D> {
-    let mut a_bits = a.get_bits();
-    a_bits.push(builder.constant_bool(false));
-    let rotate7 = rotate32(7);
-    let rotate18 = rotate32(18);
-    let shift3 = shift32(3);
-    let res_bits = xor3_with_permutation(builder, &a_bits, &rotate7, &rotate18, &shift3);
-    LazyU32WithBits::from_bits(builder, res_bits)
+    a: &LazyU32WithBits,
+) -> LazyU32WithBits {
+    let a_bits = a.get_bits(builder);
+    let res_bits = builder.add_sigma(SigmaKind::Sigma0, &a_bits);
+    LazyU32WithBits::from_bits(res_bits)
 }
 
 //#define sigma1(x)    (ROTATE((x),17) ^ ROTATE((x),19) ^ ((x)>>10))
 fn sigma1_lazy<F: RichField + Extendable<D>, const D: usize>(
     builder: &mut CircuitBuilder<F, D>,
-    a: &LazyU32WithBits<F, D>,
-) -> LazyU32WithBits<F, D> {
-    let mut a_bits = a.get_bits();
-    a_bits.push(builder.constant_bool(false));
-    let rotate17 = rotate32(17);
-    let rotate19 = rotate32(19);
-    let shift10 = shift32(10);
-    let res_bits = xor3_with_permutation(builder, &a_bits, &rotate17, &rotate19, &shift10);
-    LazyU32WithBits::from_bits(builder, res_bits)
+    a: &LazyU32WithBits,
+) -> LazyU32WithBits {
+    let a_bits = a.get_bits(builder);
+    let res_bits = builder.add_sigma(SigmaKind::Sigma1, &a_bits);
+    LazyU32WithBits::from_bits(res_bits)
 }
 
 /*
@@ -258,19 +173,19 @@ ch = a&b ^ (!a)&c
  */
 fn ch_lazy<F: RichField + Extendable<D>, const D: usize>(
     builder: &mut CircuitBuilder<F, D>,
-    a: &LazyU32WithBits<F, D>,
-    b: &LazyU32WithBits<F, D>,
-    c: &LazyU32WithBits<F, D>,
-) -> LazyU32WithBits<F, D> {
-    let a_bits = a.get_bits();
-    let b_bits = b.get_bits();
-    let c_bits = c.get_bits();
+    a: &LazyU32WithBits,
+    b: &LazyU32WithBits,
+    c: &LazyU32WithBits,
+) -> LazyU32WithBits {
+    let a_bits = a.get_bits(builder);
+    let b_bits = b.get_bits(builder);
+    let c_bits = c.get_bits(builder);
 
     let mut res_bits = Vec::new();
     for i in 0..CHUNK_SIZE {
         res_bits.push(builder.add_ch(a_bits[i], b_bits[i], c_bits[i]));
     }
-    LazyU32WithBits::from_bits(builder, res_bits)
+    LazyU32WithBits::from_bits(res_bits)
 }
 
 /*
@@ -282,413 +197,1619 @@ where m = b*c
  */
 fn maj_lazy<F: RichField + Extendable<D>, const D: usize>(
     builder: &mut CircuitBuilder<F, D>,
-    a: &LazyU32WithBits<F, D>,
-    b: &LazyU32WithBits<F, D>,
-    c: &LazyU32WithBits<F, D>,
-) -> LazyU32WithBits<F, D> {
-    let a_bits = a.get_bits();
-    let b_bits = b.get_bits();
-    let c_bits = c.get_bits();
+    a: &LazyU32WithBits,
+    b: &LazyU32WithBits,
+    c: &LazyU32WithBits,
+) -> LazyU32WithBits {
+    let a_bits = a.get_bits(builder);
+    let b_bits = b.get_bits(builder);
+    let c_bits = c.get_bits(builder);
 
     let mut res_bits = Vec::new();
     for i in 0..CHUNK_SIZE {
         res_bits.push(builder.add_maj(a_bits[i], b_bits[i], c_bits[i]));
     }
 
-    LazyU32WithBits::from_bits(builder, res_bits)
+    LazyU32WithBits::from_bits(res_bits)
 }
 
 fn add_u32_lazy<F: RichField + Extendable<D>, const D: usize>(
     builder: &mut CircuitBuilder<F, D>,
-    a: &LazyU32WithBits<F, D>,
-    b: &LazyU32WithBits<F, D>,
-) -> LazyU32WithBits<F, D> {
+    a: &LazyU32WithBits,
+    b: &LazyU32WithBits,
+) -> LazyU32WithBits {
     // Only get U32 representations for addition
-    let (res, _carry) = builder.add_u32(a.get_u32(), b.get_u32());
-    LazyU32WithBits::from_u32(builder, res)
+    let a = a.get_u32(builder);
+    let b = b.get_u32(builder);
+    let (res, _carry) = builder.add_u32(a, b);
+    LazyU32WithBits::from_u32(res)
 }
 
 fn add_many_u32_lazy<F: RichField + Extendable<D>, const D: usize>(
     builder: &mut CircuitBuilder<F, D>,
-    to_add: &[LazyU32WithBits<F, D>],
-) -> LazyU32WithBits<F, D> {
+    to_add: &[LazyU32WithBits],
+) -> LazyU32WithBits {
     let mut u32_values = Vec::new();
     for lazy_u32 in to_add {
-        u32_values.push(lazy_u32.get_u32());
+        u32_values.push(lazy_u32.get_u32(builder));
     }
     let (res, _carry) = builder.add_many_u32(&u32_values[..]);
-    LazyU32WithBits::from_u32(builder, res)
+    LazyU32WithBits::from_u32(res)
 }
 
-pub struct Sha256Targets {
-    pub message: Vec<BoolTarget>,
-    pub digest: Vec<BoolTarget>,
+/// Round functions of the SHA-256 compression over some representation of
+/// 32-bit words. `compress_with` runs the message schedule and the rounds on
+/// top of them, so backends only differ in how these are arithmetized.
+pub trait Sha256RoundFunctions<F: RichField + Extendable<D>, const D: usize> {
+    type Word: Clone;
+
+    /// `x` must be range-checked to 32 bits.
+    fn u32_to_word(&mut self, builder: &mut CircuitBuilder<F, D>, x: U32Target) -> Self::Word;
+    fn word_to_u32(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> U32Target;
+
+    fn big_sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word;
+    fn big_sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word;
+    fn sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word;
+    fn sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word;
+    fn ch(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        e: &Self::Word,
+        f: &Self::Word,
+        g: &Self::Word,
+    ) -> Self::Word;
+    fn maj(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        a: &Self::Word,
+        b: &Self::Word,
+        c: &Self::Word,
+    ) -> Self::Word;
+
+    /// `a + b mod 2^32`.
+    fn add(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        a: &Self::Word,
+        b: &Self::Word,
+    ) -> Self::Word {
+        let a = self.word_to_u32(builder, a);
+        let b = self.word_to_u32(builder, b);
+        let (sum, _carry) = builder.add_u32(a, b);
+        self.u32_to_word(builder, sum)
+    }
+
+    /// Sum of `xs` modulo `2^32`.
+    fn add_many(&mut self, builder: &mut CircuitBuilder<F, D>, xs: &[Self::Word]) -> Self::Word {
+        let xs: Vec<_> = xs.iter().map(|x| self.word_to_u32(builder, x)).collect();
+        let (sum, _carry) = builder.add_many_u32(&xs);
+        self.u32_to_word(builder, sum)
+    }
+
+    /// The new `(a, e)` of a round, if the backend computes all of its
+    /// additions at once. With `None`, `compress_with` chains `add`s.
+    fn fused_round(
+        &mut self,
+        _builder: &mut CircuitBuilder<F, D>,
+        _terms: &RoundTerms<Self::Word>,
+    ) -> Option<(Self::Word, Self::Word)> {
+        None
+    }
 }
 
-// padded_msg_len = block_count x 512 bits
-// Size: msg_len_in_bits (L) |  p bits   | 64 bits
-// Bits:      msg            | 100...000 |    L
-pub fn make_circuits<F: RichField + Extendable<D>, const D: usize>(
-    builder: &mut CircuitBuilder<F, D>,
-    msg_len_in_bits: u64,
-) -> Sha256Targets {
-    let mut message = Vec::new();
-    let mut digest = Vec::new();
-    let block_count = (msg_len_in_bits + 65 + 511) / 512;
-    let padded_msg_len = 512 * block_count;
-    let p = padded_msg_len - 64 - msg_len_in_bits;
-    assert!(p > 1);
+/// The words a round adds up: the new `a` is `t1 + t2` and the new `e` is
+/// `d + t1`, with `t1 = h + Σ1(e) + Ch(e, f, g) + k + w` and
+/// `t2 = Σ0(a) + Maj(a, b, c)`.
+pub struct RoundTerms<'a, W> {
+    pub d: &'a W,
+    pub h: &'a W,
+    pub big_sigma1_e: &'a W,
+    pub ch_e_f_g: &'a W,
+    pub k: &'a W,
+    pub w: &'a W,
+    pub big_sigma0_a: &'a W,
+    pub maj_a_b_c: &'a W,
+}
 
-    for _ in 0..msg_len_in_bits {
-        message.push(builder.add_virtual_bool_target_unsafe());
+/// Runs the round functions of `R`, but computes each round's additions in
+/// one `Sha256RoundGate` op instead of a chain of `U32AddManyGate` and
+/// `U32ArithmeticGate` ops.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FusedRounds<R>(pub R);
+
+impl<F, const D: usize, R> Sha256RoundFunctions<F, D> for FusedRounds<R>
+where
+    F: RichField + Extendable<D>,
+    R: Sha256RoundFunctions<F, D>,
+{
+    type Word = R::Word;
+
+    fn u32_to_word(&mut self, builder: &mut CircuitBuilder<F, D>, x: U32Target) -> Self::Word {
+        self.0.u32_to_word(builder, x)
     }
-    message.push(builder.constant_bool(true));
-    for _ in 0..p - 1 {
-        message.push(builder.constant_bool(false));
+
+    fn word_to_u32(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> U32Target {
+        self.0.word_to_u32(builder, x)
     }
-    for i in 0..64 {
-        let b = (msg_len_in_bits >> (63 - i)) & 1;
-        message.push(builder.constant_bool(b == 1));
+
+    fn big_sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        self.0.big_sigma0(builder, x)
     }
 
-    // init states with lazy evaluation
-    let mut state = Vec::new();
-    for c in &H256 {
-        let u32_target = builder.constant_u32(*c);
-        state.push(LazyU32WithBits::from_u32(builder, u32_target));
+    fn big_sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        self.0.big_sigma1(builder, x)
     }
 
-    let mut k256 = Vec::new();
-    for k in &K256 {
-        k256.push(builder.constant_u32(*k));
+    fn sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        self.0.sigma0(builder, x)
     }
-    for blk in 0..block_count {
-        let mut x = Vec::new();
-
-        // Clone state variables
-        let mut a = state[0].clone();
-        let mut b = state[1].clone();
-        let mut c = state[2].clone();
-        let mut d = state[3].clone();
-        let mut e = state[4].clone();
-        let mut f = state[5].clone();
-        let mut g = state[6].clone();
-        let mut h = state[7].clone();
-
-        for i in 0..16 {
-            let index = blk as usize * 512 + i * 32;
-            let u32_target = builder.le_sum_optimized(message[index..index + 32].iter().rev());
-            x.push(LazyU32WithBits::from_u32(builder, U32Target(u32_target)));
-
-            let mut t1 = h.clone();
-            let big_sigma1_e = big_sigma1_lazy(builder, &e);
-            let ch_e_f_g = ch_lazy(builder, &e, &f, &g);
-            let k256_lazy = LazyU32WithBits::from_u32(builder, k256[i]);
-            t1 = add_many_u32_lazy(
-                builder,
-                &[t1, big_sigma1_e, ch_e_f_g, k256_lazy, x[i].clone()],
-            );
 
-            let mut t2 = big_sigma0_lazy(builder, &a);
-            let maj_a_b_c = maj_lazy(builder, &a, &b, &c);
-            t2 = add_u32_lazy(builder, &t2, &maj_a_b_c);
+    fn sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        self.0.sigma1(builder, x)
+    }
 
-            h = g;
-            g = f;
-            f = e;
-            e = add_u32_lazy(builder, &d, &t1);
-            d = c;
-            c = b;
-            b = a;
-            a = add_u32_lazy(builder, &t1, &t2);
-        }
+    fn ch(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        e: &Self::Word,
+        f: &Self::Word,
+        g: &Self::Word,
+    ) -> Self::Word {
+        self.0.ch(builder, e, f, g)
+    }
 
-        for i in 16..64 {
-            let s0 = sigma0_lazy(builder, &x[(i + 1) & 0x0f]);
-            let s1 = sigma1_lazy(builder, &x[(i + 14) & 0x0f]);
+    fn maj(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        a: &Self::Word,
+        b: &Self::Word,
+        c: &Self::Word,
+    ) -> Self::Word {
+        self.0.maj(builder, a, b, c)
+    }
 
-            let s0_add_s1 = add_u32_lazy(builder, &s0, &s1);
-            let s0_add_s1_add_x = add_u32_lazy(builder, &s0_add_s1, &x[(i + 9) & 0xf]);
-            x[i & 0xf] = add_u32_lazy(builder, &x[i & 0xf], &s0_add_s1_add_x);
+    fn add(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        a: &Self::Word,
+        b: &Self::Word,
+    ) -> Self::Word {
+        self.0.add(builder, a, b)
+    }
 
-            let big_sigma0_a = big_sigma0_lazy(builder, &a);
-            let big_sigma1_e = big_sigma1_lazy(builder, &e);
-            let ch_e_f_g = ch_lazy(builder, &e, &f, &g);
-            let maj_a_b_c = maj_lazy(builder, &a, &b, &c);
+    fn add_many(&mut self, builder: &mut CircuitBuilder<F, D>, xs: &[Self::Word]) -> Self::Word {
+        self.0.add_many(builder, xs)
+    }
 
-            let h_add_sigma1 = add_u32_lazy(builder, &h, &big_sigma1_e);
-            let h_add_sigma1_add_ch_e_f_g = add_u32_lazy(builder, &h_add_sigma1, &ch_e_f_g);
-            let k256_lazy = LazyU32WithBits::from_u32(builder, k256[i]);
-            let h_add_sigma1_add_ch_e_f_g_add_k256 =
-                add_u32_lazy(builder, &h_add_sigma1_add_ch_e_f_g, &k256_lazy);
+    fn fused_round(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        terms: &RoundTerms<Self::Word>,
+    ) -> Option<(Self::Word, Self::Word)> {
+        let inputs = [
+            terms.d,
+            terms.h,
+            terms.big_sigma1_e,
+            terms.ch_e_f_g,
+            terms.k,
+            terms.w,
+            terms.big_sigma0_a,
+            terms.maj_a_b_c,
+        ]
+        .map(|x| self.0.word_to_u32(builder, x));
+        let (new_a, new_e) = builder.add_sha256_round(inputs);
+        Some((
+            self.0.u32_to_word(builder, new_a),
+            self.0.u32_to_word(builder, new_e),
+        ))
+    }
+}
 
-            let t1 = add_u32_lazy(builder, &x[i & 0xf], &h_add_sigma1_add_ch_e_f_g_add_k256);
-            let t2 = add_u32_lazy(builder, &big_sigma0_a, &maj_a_b_c);
+/// The bit-level backend: words are decomposed into bits once and combined
+/// with `SigmaGate`, `ChGate` and `MajGate`. One `SigmaGate` op computes a
+/// whole σ, which took 32 `Xor3Gate` ops; `test_sigma_gate_saves_rows`
+/// checks that this takes fewer rows.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BitRoundFunctions;
 
-            h = g;
-            g = f;
-            f = e;
-            e = add_u32_lazy(builder, &d, &t1);
-            d = c;
-            c = b;
-            b = a;
-            a = add_u32_lazy(builder, &t1, &t2);
-        }
+impl<F: RichField + Extendable<D>, const D: usize> Sha256RoundFunctions<F, D>
+    for BitRoundFunctions
+{
+    type Word = LazyU32WithBits;
 
-        state[0] = add_u32_lazy(builder, &state[0], &a);
-        state[1] = add_u32_lazy(builder, &state[1], &b);
-        state[2] = add_u32_lazy(builder, &state[2], &c);
-        state[3] = add_u32_lazy(builder, &state[3], &d);
-        state[4] = add_u32_lazy(builder, &state[4], &e);
-        state[5] = add_u32_lazy(builder, &state[5], &f);
-        state[6] = add_u32_lazy(builder, &state[6], &g);
-        state[7] = add_u32_lazy(builder, &state[7], &h);
+    fn u32_to_word(&mut self, _builder: &mut CircuitBuilder<F, D>, x: U32Target) -> Self::Word {
+        LazyU32WithBits::from_u32(x)
     }
 
-    // Only decompose to bits for the final digest output
-    for i in 0..8 {
-        let bits = state[i].get_bits();
-        digest.extend_from_slice(&bits);
+    fn word_to_u32(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> U32Target {
+        x.get_u32(builder)
     }
 
-    Sha256Targets { message, digest }
-}
+    fn big_sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        big_sigma0_lazy(builder, x)
+    }
 
-pub struct VariableLengthSha256Targets {
-    pub message: Vec<BoolTarget>,
-    pub digest: Vec<BoolTarget>,
-    pub msg_len: U32Target,
-    pub msg_blocks: U32Target,
-}
+    fn big_sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        big_sigma1_lazy(builder, x)
+    }
 
-pub fn make_variable_length_circuits<F: RichField + Extendable<D>, const D: usize>(
-    builder: &mut CircuitBuilder<F, D>,
-    max_total_bits: usize,
-) -> VariableLengthSha256Targets {
-    assert!(
-        max_total_bits % 512 == 0,
-        "max_total_bits must be a multiple of 512 (got {})",
-        max_total_bits
-    );
-    let tot_blocks = max_total_bits / 512;
-    let mut message = Vec::new();
-    let mut digest = Vec::new();
+    fn sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        sigma0_lazy(builder, x)
+    }
 
-    let msg_len = builder.add_virtual_u32_target();
-    let msg_blocks = builder.add_virtual_u32_target();
+    fn sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        sigma1_lazy(builder, x)
+    }
 
-    for _ in 0..max_total_bits {
-        message.push(builder.add_virtual_bool_target_unsafe());
+    fn ch(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        e: &Self::Word,
+        f: &Self::Word,
+        g: &Self::Word,
+    ) -> Self::Word {
+        ch_lazy(builder, e, f, g)
     }
 
-    // init states with lazy evaluation
-    let mut state = Vec::new();
-    for c in &H256 {
-        let u32_target = builder.constant_u32(*c);
-        state.push(LazyU32WithBits::from_u32(builder, u32_target));
+    fn maj(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        a: &Self::Word,
+        b: &Self::Word,
+        c: &Self::Word,
+    ) -> Self::Word {
+        maj_lazy(builder, a, b, c)
+    }
+
+    fn add(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        a: &Self::Word,
+        b: &Self::Word,
+    ) -> Self::Word {
+        add_u32_lazy(builder, a, b)
+    }
+
+    fn add_many(&mut self, builder: &mut CircuitBuilder<F, D>, xs: &[Self::Word]) -> Self::Word {
+        add_many_u32_lazy(builder, xs)
     }
+}
 
+/// Runs the SHA-256 compression function on the 16-word block `w`, returning
+/// the updated state.
+pub fn compress_with<F, const D: usize, R>(
+    builder: &mut CircuitBuilder<F, D>,
+    round_functions: &mut R,
+    state: &[R::Word],
+    w: &[U32Target],
+) -> Vec<R::Word>
+where
+    F: RichField + Extendable<D>,
+    R: Sha256RoundFunctions<F, D>,
+{
+    assert_eq!(state.len(), 8);
+    assert_eq!(w.len(), 16);
     let mut k256 = Vec::new();
     for k in &K256 {
         k256.push(builder.constant_u32(*k));
     }
+    let rf = round_functions;
+
+    let mut x = Vec::new();
+    let mut state = state.to_vec();
+
+    // Clone state variables
+    let mut a = state[0].clone();
+    let mut b = state[1].clone();
+    let mut c = state[2].clone();
+    let mut d = state[3].clone();
+    let mut e = state[4].clone();
+    let mut f = state[5].clone();
+    let mut g = state[6].clone();
+    let mut h = state[7].clone();
+
+    for i in 0..16 {
+        x.push(rf.u32_to_word(builder, w[i]));
+
+        let big_sigma1_e = rf.big_sigma1(builder, &e);
+        let ch_e_f_g = rf.ch(builder, &e, &f, &g);
+        let k = rf.u32_to_word(builder, k256[i]);
+        let big_sigma0_a = rf.big_sigma0(builder, &a);
+        let maj_a_b_c = rf.maj(builder, &a, &b, &c);
+
+        let terms = RoundTerms {
+            d: &d,
+            h: &h,
+            big_sigma1_e: &big_sigma1_e,
+            ch_e_f_g: &ch_e_f_g,
+            k: &k,
+            w: &x[i],
+            big_sigma0_a: &big_sigma0_a,
+            maj_a_b_c: &maj_a_b_c,
+        };
+        let (new_a, new_e) = match rf.fused_round(builder, &terms) {
+            Some(new) => new,
+            None => {
+                let t1 = rf.add_many(builder, &[h, big_sigma1_e, ch_e_f_g, k, x[i].clone()]);
+                let t2 = rf.add(builder, &big_sigma0_a, &maj_a_b_c);
+                let new_e = rf.add(builder, &d, &t1);
+                (rf.add(builder, &t1, &t2), new_e)
+            }
+        };
 
-    let mut do_block = builder.constant_bool(true);
-    for blk in 0..tot_blocks {
-        let mut x = Vec::new();
-
-        // Clone state variables
-        let mut a = state[0].clone();
-        let mut b = state[1].clone();
-        let mut c = state[2].clone();
-        let mut d = state[3].clone();
-        let mut e = state[4].clone();
-        let mut f = state[5].clone();
-        let mut g = state[6].clone();
-        let mut h = state[7].clone();
-
-        let blk_target = builder.constant_u32(blk as u32);
-
-        let after_msg_block = builder.is_equal(blk_target.0, msg_blocks.0);
-        let not_after_msg_block = builder.not(after_msg_block);
-        do_block = builder.and(do_block, not_after_msg_block);
+        h = g;
+        g = f;
+        f = e;
+        e = new_e;
+        d = c;
+        c = b;
+        b = a;
+        a = new_a;
+    }
 
-        for i in 0..16 {
-            let index = blk as usize * 512 + i * 32;
-            let u32_target = builder.le_sum_optimized(message[index..index + 32].iter().rev());
-            x.push(LazyU32WithBits::from_u32(builder, U32Target(u32_target)));
-
-            let mut t1 = h.clone();
-            let big_sigma1_e = big_sigma1_lazy(builder, &e);
-            t1 = add_u32_lazy(builder, &t1, &big_sigma1_e);
-            let ch_e_f_g = ch_lazy(builder, &e, &f, &g);
-            t1 = add_u32_lazy(builder, &t1, &ch_e_f_g);
-            let k256_lazy = LazyU32WithBits::from_u32(builder, k256[i]);
-            t1 = add_u32_lazy(builder, &t1, &k256_lazy);
-            t1 = add_u32_lazy(builder, &t1, &x[i]);
-
-            let mut t2 = big_sigma0_lazy(builder, &a);
-            let maj_a_b_c = maj_lazy(builder, &a, &b, &c);
-            t2 = add_u32_lazy(builder, &t2, &maj_a_b_c);
-
-            h = g;
-            g = f;
-            f = e;
-            e = add_u32_lazy(builder, &d, &t1);
-            d = c;
-            c = b;
-            b = a;
-            a = add_u32_lazy(builder, &t1, &t2);
-        }
-
-        for i in 16..64 {
-            let s0 = sigma0_lazy(builder, &x[(i + 1) & 0x0f]);
-            let s1 = sigma1_lazy(builder, &x[(i + 14) & 0x0f]);
-
-            let s0_add_s1 = add_u32_lazy(builder, &s0, &s1);
-            let s0_add_s1_add_x = add_u32_lazy(builder, &s0_add_s1, &x[(i + 9) & 0xf]);
-            x[i & 0xf] = add_u32_lazy(builder, &x[i & 0xf], &s0_add_s1_add_x);
-
-            let big_sigma0_a = big_sigma0_lazy(builder, &a);
-            let big_sigma1_e = big_sigma1_lazy(builder, &e);
-            let ch_e_f_g = ch_lazy(builder, &e, &f, &g);
-            let maj_a_b_c = maj_lazy(builder, &a, &b, &c);
-
-            let h_add_sigma1 = add_u32_lazy(builder, &h, &big_sigma1_e);
-            let h_add_sigma1_add_ch_e_f_g = add_u32_lazy(builder, &h_add_sigma1, &ch_e_f_g);
-            let k256_lazy = LazyU32WithBits::from_u32(builder, k256[i]);
-            let h_add_sigma1_add_ch_e_f_g_add_k256 =
-                add_u32_lazy(builder, &h_add_sigma1_add_ch_e_f_g, &k256_lazy);
-
-            let t1 = add_u32_lazy(builder, &x[i & 0xf], &h_add_sigma1_add_ch_e_f_g_add_k256);
-            let t2 = add_u32_lazy(builder, &big_sigma0_a, &maj_a_b_c);
-
-            h = g;
-            g = f;
-            f = e;
-            e = add_u32_lazy(builder, &d, &t1);
-            d = c;
-            c = b;
-            b = a;
-            a = add_u32_lazy(builder, &t1, &t2);
-        }
-
-        let z = [a, b, c, d, e, f, g, h];
+    for i in 16..64 {
+        let s0 = rf.sigma0(builder, &x[(i + 1) & 0x0f]);
+        let s1 = rf.sigma1(builder, &x[(i + 14) & 0x0f]);
+
+        let s0_add_s1 = rf.add(builder, &s0, &s1);
+        let s0_add_s1_add_x = rf.add(builder, &s0_add_s1, &x[(i + 9) & 0xf]);
+        x[i & 0xf] = rf.add(builder, &x[i & 0xf], &s0_add_s1_add_x);
+
+        let big_sigma0_a = rf.big_sigma0(builder, &a);
+        let big_sigma1_e = rf.big_sigma1(builder, &e);
+        let ch_e_f_g = rf.ch(builder, &e, &f, &g);
+        let maj_a_b_c = rf.maj(builder, &a, &b, &c);
+
+        let k = rf.u32_to_word(builder, k256[i]);
+
+        let terms = RoundTerms {
+            d: &d,
+            h: &h,
+            big_sigma1_e: &big_sigma1_e,
+            ch_e_f_g: &ch_e_f_g,
+            k: &k,
+            w: &x[i & 0xf],
+            big_sigma0_a: &big_sigma0_a,
+            maj_a_b_c: &maj_a_b_c,
+        };
+        let (new_a, new_e) = match rf.fused_round(builder, &terms) {
+            Some(new) => new,
+            None => {
+                let h_add_sigma1 = rf.add(builder, &h, &big_sigma1_e);
+                let h_add_sigma1_add_ch_e_f_g = rf.add(builder, &h_add_sigma1, &ch_e_f_g);
+                let h_add_sigma1_add_ch_e_f_g_add_k256 =
+                    rf.add(builder, &h_add_sigma1_add_ch_e_f_g, &k);
+
+                let t1 = rf.add(builder, &x[i & 0xf], &h_add_sigma1_add_ch_e_f_g_add_k256);
+                let t2 = rf.add(builder, &big_sigma0_a, &maj_a_b_c);
+                let new_e = rf.add(builder, &d, &t1);
+                (rf.add(builder, &t1, &t2), new_e)
+            }
+        };
 
-        for i in 0..8 {
-            let new_state = add_u32_lazy(builder, &state[i], &z[i]);
-            // Use select to conditionally update state based on do_block
-            let new_u32 = builder.select(do_block, new_state.get_u32().0, state[i].get_u32().0);
-            state[i] = LazyU32WithBits::from_u32(builder, U32Target(new_u32));
-        }
+        h = g;
+        g = f;
+        f = e;
+        e = new_e;
+        d = c;
+        c = b;
+        b = a;
+        a = new_a;
     }
 
-    // Only decompose to bits for the final digest output
-    for i in 0..8 {
-        let bits = state[i].get_bits();
-        digest.extend_from_slice(&bits);
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *s = rf.add(builder, s, &v);
     }
 
-    VariableLengthSha256Targets {
-        message,
-        digest,
-        msg_len,
-        msg_blocks,
-    }
+    state
 }
-pub fn fill_variable_length_circuits<F: RichField + Extendable<D>, const D: usize>(
-    pw: &mut PartialWitness<F>,
-    msg: &[u8],
-    max_total_bits: usize,
-    targets: &VariableLengthSha256Targets,
-) -> Result<()> {
-    assert!(
-        max_total_bits % 512 == 0,
-        "max_total_bits must be a multiple of 512 (got {})",
-        max_total_bits
-    );
 
-    let msg_bits = array_to_bits(msg);
-    let msg_blocks = (msg_bits.len() + 65 + 511) / 512;
-    let msg_bits_len = msg_bits.len();
+/// `compress_with` on the bit-level backend.
+fn compress_lazy<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    state: &[LazyU32WithBits],
+    w: &[U32Target],
+) -> Vec<LazyU32WithBits> {
+    compress_with(builder, &mut BitRoundFunctions, state, w)
+}
 
-    assert_eq!(
-        max_total_bits,
-        targets.message.len(),
-        "max_total_bits ({}) must match message target length ({})",
-        max_total_bits,
-        targets.message.len()
-    );
-    assert!(
-        max_total_bits >= msg_blocks * 512,
-        "Message too long: needs {} bits but circuit only supports {} bits",
-        msg_blocks * 512,
-        max_total_bits
-    );
+/// Runs the SHA-256 compression function on `block`, returning the updated
+/// state. Inputs must be range-checked to 32 bits.
+pub fn sha256_compress<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    state: [U32Target; 8],
+    block: [U32Target; 16],
+) -> [U32Target; 8] {
+    sha256_compress_with(builder, &mut BitRoundFunctions, state, block)
+}
 
-    pw.set_u32_target(targets.msg_len, msg_bits_len as u32)?;
-    pw.set_u32_target(targets.msg_blocks, msg_blocks as u32)?;
-
-    for i in 0..max_total_bits {
-        let bit = if i < msg_bits_len {
-            msg_bits[i]
-        } else if i == msg_bits_len {
-            true // the mandatory `1` bit
-        } else if i >= msg_blocks * 512 - 64 {
-            // length encoding, big-endian
-            ((msg_bits_len >> (msg_blocks * 512 - i - 1)) & 1) == 1
-        } else {
-            false
-        };
-        pw.set_bool_target(targets.message[i], bit)?;
-    }
-    Ok(())
+/// Like `sha256_compress`, with the round functions of another backend.
+pub fn sha256_compress_with<F, const D: usize, R>(
+    builder: &mut CircuitBuilder<F, D>,
+    round_functions: &mut R,
+    state: [U32Target; 8],
+    block: [U32Target; 16],
+) -> [U32Target; 8]
+where
+    F: RichField + Extendable<D>,
+    R: Sha256RoundFunctions<F, D>,
+{
+    let state: Vec<_> = state
+        .iter()
+        .map(|&s| round_functions.u32_to_word(builder, s))
+        .collect();
+    let new_state = compress_with(builder, round_functions, &state, &block);
+    core::array::from_fn(|i| round_functions.word_to_u32(builder, &new_state[i]))
 }
 
-#[cfg(test)]
-pub mod tests {
-    use plonky2::{
-        iop::witness::{PartialWitness, WitnessWrite},
-        plonk::{
-            circuit_builder::CircuitBuilder,
-            circuit_data::CircuitConfig,
-            config::{GenericConfig, PoseidonGoldilocksConfig},
-        },
-    };
-    use sha2::Digest;
+/// Native counterpart of `sha256_compress`.
+pub fn sha256_compress_native(state: [u32; 8], block: [u32; 16]) -> [u32; 8] {
+    let mut w = [0u32; 64];
+    w[..16].copy_from_slice(&block);
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
 
-    use crate::circuit::{
-        array_to_bits, fill_variable_length_circuits, make_circuits, make_variable_length_circuits,
-        EXAMPLE_MESSAGE,
-    };
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+    for i in 0..64 {
+        let big_sigma1_e = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(big_sigma1_e)
+            .wrapping_add(ch)
+            .wrapping_add(K256[i])
+            .wrapping_add(w[i]);
+        let big_sigma0_a = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = big_sigma0_a.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
 
-    #[test]
-    fn test_sha256_circuit() -> anyhow::Result<()> {
-        const D: usize = 2;
-        type C = PoseidonGoldilocksConfig;
-        type F = <C as GenericConfig<D>>::F;
-        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::wide_ecc_config());
+    let z = [a, b, c, d, e, f, g, h];
+    core::array::from_fn(|i| state[i].wrapping_add(z[i]))
+}
 
-        let msg = EXAMPLE_MESSAGE;
+/// Packs the padded message into big-endian 32-bit words. Every bit becomes a
+/// limb of a `BaseSumGateOptimized<2>`, whose `x * (x - 1)` constraint is what
+/// makes the message targets (allocated with `add_virtual_bool_target_unsafe`)
+/// boolean.
+pub(crate) fn message_words<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    message: &[BoolTarget],
+) -> Vec<U32Target> {
+    message
+        .chunks(32)
+        .map(|word| U32Target(builder.le_sum_optimized(word.iter().rev())))
+        .collect()
+}
 
-        let digest = sha2::Sha256::digest(&msg);
-        let msg_len_in_bits = (msg.len() * 8) as usize;
+pub struct Sha256Targets {
+    /// Message bits, constrained to be boolean by `message_words`.
+    pub message: Vec<BoolTarget>,
+    pub digest: Vec<BoolTarget>,
+}
 
-        // Create SHA256 circuit using your make_circuits function
-        let sha256_targets = make_circuits(&mut builder, msg_len_in_bits as u64);
+/// Like `Sha256Targets`, with the digest as eight big-endian words.
+pub struct Sha256U32Targets {
+    /// Message bits, constrained to be boolean by `message_words`.
+    pub message: Vec<BoolTarget>,
+    pub digest: [U32Target; 8],
+}
 
-        // Create witness
-        let mut pw = PartialWitness::new();
+/// Decomposes digest words into 256 big-endian bits.
+pub(crate) fn digest_to_bits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    digest: &[U32Target],
+) -> Vec<BoolTarget> {
+    let mut bits = Vec::new();
+    for word in digest {
+        bits.extend(u32_to_bits_target::<F, D, 2>(builder, word));
+    }
+    bits
+}
 
-        // Convert message bytes to bits and set as witness
-        let msg_bits = array_to_bits(&msg);
-        for (i, &bit) in msg_bits.iter().enumerate().take(msg_len_in_bits as usize) {
-            pw.set_bool_target(sha256_targets.message[i], bit)?;
-        }
+/// Packs the digest into four field elements, two words each
+/// (`digest[2i] * 2^32 + digest[2i + 1]`), e.g. to feed it to Poseidon or
+/// compare it against a public input cheaply.
+///
+/// The high words are constrained not to be `0xffffffff`, so every packed
+/// value is below the Goldilocks order and the packing is injective. Digests
+/// with such a word (about one in 2^30) cannot be packed; circuits that must
+/// handle every digest should compare the words instead.
+pub fn digest_to_hash_out<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    digest: &[U32Target; 8],
+) -> HashOutTarget {
+    let base = F::from_canonical_u64(1 << 32);
+    let hi_max = builder.constant(F::from_canonical_u32(u32::MAX));
+    let elements = core::array::from_fn(|i| {
+        let hi_is_max = builder.is_equal(digest[2 * i].0, hi_max);
+        builder.assert_zero(hi_is_max.target);
+        builder.mul_const_add(base, digest[2 * i].0, digest[2 * i + 1].0)
+    });
+    HashOutTarget { elements }
+}
 
-        // Convert expected digest bytes to bits and set as witness
-        let expected_digest_bits = array_to_bits(&digest);
-        for (i, expected_digest_bit) in expected_digest_bits.iter().enumerate() {
+/// Native counterpart of `digest_to_hash_out`. Panics if a high word is
+/// `0xffffffff`.
+pub fn digest_to_hash_out_native<F: RichField>(digest: &[u32; 8]) -> HashOut<F> {
+    let elements = core::array::from_fn(|i| {
+        assert_ne!(
+            digest[2 * i],
+            u32::MAX,
+            "digest word {} cannot be packed",
+            2 * i
+        );
+        F::from_canonical_u64(((digest[2 * i] as u64) << 32) | digest[2 * i + 1] as u64)
+    });
+    HashOut { elements }
+}
+
+// padded_msg_len = block_count x 512 bits
+// Size: msg_len_in_bits (L) |  p bits   | 64 bits
+// Bits:      msg            | 100...000 |    L
+fn fixed_length_message<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+) -> (Vec<BoolTarget>, Vec<U32Target>) {
+    let mut message = Vec::new();
+    let block_count = (msg_len_in_bits + 65 + 511) / 512;
+    let padded_msg_len = 512 * block_count;
+    let p = padded_msg_len - 64 - msg_len_in_bits;
+    assert!(p > 1);
+
+    for _ in 0..msg_len_in_bits {
+        message.push(builder.add_virtual_bool_target_unsafe());
+    }
+    message.push(builder.constant_bool(true));
+    for _ in 0..p - 1 {
+        message.push(builder.constant_bool(false));
+    }
+    for i in 0..64 {
+        let b = (msg_len_in_bits >> (63 - i)) & 1;
+        message.push(builder.constant_bool(b == 1));
+    }
+    let words = message_words(builder, &message);
+    (message, words)
+}
+
+fn make_circuits_with_iv<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+    iv: &[u32; 8],
+) -> Sha256U32Targets {
+    let (message, words) = fixed_length_message(builder, msg_len_in_bits);
+    let digest = sha256_padded_words(builder, iv, &words);
+
+    Sha256U32Targets { message, digest }
+}
+
+pub fn make_circuits_u32<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+) -> Sha256U32Targets {
+    make_circuits_with_iv(builder, msg_len_in_bits, &H256)
+}
+
+/// How the round functions are arithmetized; see `Sha256RoundFunctions`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Sha256Backend {
+    /// `BitRoundFunctions`: one custom gate op per bit.
+    #[default]
+    Bits,
+    /// `LookupRoundFunctions`: byte-sized spread limbs and lookup tables.
+    /// The limb recompositions take more rows per block than `Bits`, on top
+    /// of the tables.
+    Lookup,
+    /// `SpreadRoundFunctions`: spread half-words over `BaseSumGateOptimized<4>`.
+    /// It needs no bit-level custom gates, but takes more rows per block
+    /// than `Bits`.
+    Spread,
+    /// `FusedRounds<BitRoundFunctions>`: the bit-level round functions, with
+    /// each round's additions in one `Sha256RoundGate` op.
+    FusedRounds,
+}
+
+impl Sha256Backend {
+    pub const ALL: [Sha256Backend; 4] = [
+        Sha256Backend::Bits,
+        Sha256Backend::Lookup,
+        Sha256Backend::Spread,
+        Sha256Backend::FusedRounds,
+    ];
+}
+
+/// Like `make_circuits_u32`, with the round functions of `backend`. All
+/// backends compute the same digest.
+pub fn make_circuits_u32_with_backend<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+    backend: Sha256Backend,
+) -> Sha256U32Targets {
+    let (message, words) = fixed_length_message(builder, msg_len_in_bits);
+    let digest = match backend {
+        Sha256Backend::Bits => sha256_padded_words(builder, &H256, &words),
+        Sha256Backend::Lookup => {
+            sha256_padded_words_with(builder, &mut LookupRoundFunctions::default(), &H256, &words)
+        }
+        Sha256Backend::Spread => {
+            sha256_padded_words_with(builder, &mut SpreadRoundFunctions, &H256, &words)
+        }
+        Sha256Backend::FusedRounds => {
+            sha256_padded_words_with(builder, &mut FusedRounds(BitRoundFunctions), &H256, &words)
+        }
+    };
+
+    Sha256U32Targets { message, digest }
+}
+
+/// Like `make_circuits_u32`, with the digest decomposed into 256 bits.
+pub fn make_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+) -> Sha256Targets {
+    let targets = make_circuits_u32(builder, msg_len_in_bits);
+    Sha256Targets {
+        message: targets.message,
+        digest: digest_to_bits(builder, &targets.digest),
+    }
+}
+
+/// SHA-224 counterpart of `make_circuits`; the digest holds 224 bits.
+pub fn make_sha224_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+) -> Sha256Targets {
+    let targets = make_circuits_with_iv(builder, msg_len_in_bits, &H224);
+    Sha256Targets {
+        message: targets.message,
+        digest: digest_to_bits(builder, &targets.digest[..7]),
+    }
+}
+
+/// Public inputs of `make_public_circuits`: the digest as eight big-endian
+/// words, followed by what `PublicMessage` selects.
+pub const DIGEST_PUBLIC_INPUTS: Range<usize> = 0..8;
+pub const MESSAGE_PUBLIC_INPUTS_START: usize = 8;
+
+/// What `make_public_circuits` registers about the message after the digest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PublicMessage {
+    /// Nothing; the message stays private.
+    None,
+    /// The `ceil(len / 32)` big-endian words covering the message. A partial
+    /// last word carries the `1` padding bit and zero fill, see
+    /// `message_public_inputs`.
+    Words,
+    /// A Poseidon hash (`hash_n_to_hash_no_pad`) of those words, as four
+    /// field elements.
+    Commitment,
+}
+
+/// Like `make_circuits_u32`, registering the digest and (per `public_message`)
+/// the message as public inputs, in the layout described by
+/// `DIGEST_PUBLIC_INPUTS` and `MESSAGE_PUBLIC_INPUTS_START`.
+pub fn make_public_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+    public_message: PublicMessage,
+) -> Sha256U32Targets {
+    let (message, words) = fixed_length_message(builder, msg_len_in_bits);
+    let digest = sha256_padded_words(builder, &H256, &words);
+    for word in &digest {
+        builder.register_public_input(word.0);
+    }
+
+    let message_words: Vec<Target> = words[..msg_len_in_bits.div_ceil(32) as usize]
+        .iter()
+        .map(|w| w.0)
+        .collect();
+    match public_message {
+        PublicMessage::None => {}
+        PublicMessage::Words => builder.register_public_inputs(&message_words),
+        PublicMessage::Commitment => {
+            let commitment = builder.hash_n_to_hash_no_pad::<PoseidonHash>(message_words);
+            builder.register_public_inputs(&commitment.elements);
+        }
+    }
+
+    Sha256U32Targets { message, digest }
+}
+
+/// Native counterpart of the public inputs registered by `make_public_circuits`
+/// for `msg`.
+pub fn public_inputs_native<F: RichField>(msg: &[u8], public_message: PublicMessage) -> Vec<F> {
+    let digest = Sha256::digest(msg);
+    let mut public_inputs: Vec<F> = digest
+        .chunks(4)
+        .map(|word| F::from_canonical_u32(u32::from_be_bytes(word.try_into().unwrap())))
+        .collect();
+    match public_message {
+        PublicMessage::None => {}
+        PublicMessage::Words => public_inputs.extend(message_public_inputs::<F>(msg)),
+        PublicMessage::Commitment => public_inputs
+            .extend(PoseidonHash::hash_no_pad(&message_public_inputs::<F>(msg)).elements),
+    }
+    public_inputs
+}
+
+/// The message words registered by `PublicMessage::Words`: `msg` followed by
+/// the `1` padding bit, zero filled to a multiple of 32 bits, and without the
+/// word the `1` bit would start when `msg` is word aligned.
+pub fn message_public_inputs<F: RichField>(msg: &[u8]) -> Vec<F> {
+    let mut bytes = msg.to_vec();
+    bytes.push(0x80);
+    bytes.resize(msg.len().div_ceil(4) * 4, 0);
+    bytes
+        .chunks(4)
+        .map(|word| F::from_canonical_u32(u32::from_be_bytes(word.try_into().unwrap())))
+        .collect()
+}
+
+/// Reads the digest out of a proof of a `make_public_circuits` circuit.
+pub fn digest_from_proof<F, C, const D: usize>(proof: &ProofWithPublicInputs<F, C, D>) -> [u8; 32]
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest
+        .chunks_mut(4)
+        .zip(&proof.public_inputs[DIGEST_PUBLIC_INPUTS])
+    {
+        bytes.copy_from_slice(&(word.to_canonical_u64() as u32).to_be_bytes());
+    }
+    digest
+}
+
+/// SHA-256 state after hashing a block-aligned message prefix.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Sha256Midstate {
+    pub state: [u32; 8],
+    /// Number of 64-byte blocks already hashed.
+    pub blocks: u32,
+}
+
+impl Sha256Midstate {
+    /// Hashes `prefix` natively; its length must be a multiple of 64 bytes.
+    pub fn from_prefix(prefix: &[u8]) -> Self {
+        assert_eq!(
+            prefix.len() % 64,
+            0,
+            "prefix must be a multiple of 64 bytes (got {})",
+            prefix.len()
+        );
+        let mut state = H256;
+        for block in prefix.chunks(64) {
+            let words = core::array::from_fn(|i| {
+                u32::from_be_bytes(block[4 * i..4 * i + 4].try_into().unwrap())
+            });
+            state = sha256_compress_native(state, words);
+        }
+        Self {
+            state,
+            blocks: (prefix.len() / 64) as u32,
+        }
+    }
+}
+
+pub struct Sha256MidstateTargets {
+    /// Suffix bits, followed by the `1` bit and zero fill. The length is
+    /// appended as words derived from `blocks`.
+    pub message: Vec<BoolTarget>,
+    pub digest: Vec<BoolTarget>,
+    /// Public inputs, registered in this order: `state`, then `blocks`.
+    pub state: [U32Target; 8],
+    pub blocks: U32Target,
+    /// Big-endian total length in bits as `[hi, lo]` words, constrained to be
+    /// the canonical split of `512 * blocks + msg_len_in_bits`.
+    pub total_len: [U32Target; 2],
+    pub msg_len_in_bits: u64,
+}
+
+/// Hashes a `msg_len_in_bits`-bit suffix starting from `state`, with the
+/// big-endian 64-bit total length given as `[hi, lo]` words.
+pub(crate) fn make_suffix_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    state: [U32Target; 8],
+    total_len: [U32Target; 2],
+    msg_len_in_bits: u64,
+) -> (Vec<BoolTarget>, [U32Target; 8]) {
+    let message: Vec<_> = (0..msg_len_in_bits)
+        .map(|_| builder.add_virtual_bool_target_unsafe())
+        .collect();
+    hash_suffix(builder, state, total_len, &message)
+}
+
+/// Hashes the bits of `message` starting from `state`, with the big-endian
+/// 64-bit total length given as `[hi, lo]` words. Returns the message with
+/// the `1` bit and zero fill appended, and the digest. The bits are
+/// constrained to be boolean by `message_words`.
+pub(crate) fn hash_suffix<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    state: [U32Target; 8],
+    total_len: [U32Target; 2],
+    message: &[BoolTarget],
+) -> (Vec<BoolTarget>, [U32Target; 8]) {
+    let mut message = message.to_vec();
+    let msg_len_in_bits = message.len() as u64;
+    let block_count = (msg_len_in_bits + 65).div_ceil(512);
+    let p = 512 * block_count - 64 - msg_len_in_bits;
+
+    message.push(builder.constant_bool(true));
+    for _ in 0..p - 1 {
+        message.push(builder.constant_bool(false));
+    }
+    let mut words = message_words(builder, &message);
+    words.extend(total_len);
+
+    let mut lazy_state: Vec<_> = state
+        .iter()
+        .map(|&s| LazyU32WithBits::from_u32(s))
+        .collect();
+    for block in words.chunks(16) {
+        lazy_state = compress_lazy(builder, &lazy_state, block);
+    }
+    (
+        message,
+        core::array::from_fn(|i| lazy_state[i].get_u32(builder)),
+    )
+}
+
+/// Hashes a `msg_len_in_bits`-bit message suffix after a prefix whose
+/// midstate is baked into the circuit as constants. The digest is the
+/// SHA-256 of the whole message.
+pub fn make_circuits_with_midstate<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    midstate: &Sha256Midstate,
+    msg_len_in_bits: u64,
+) -> Sha256Targets {
+    let state = midstate.state.map(|s| builder.constant_u32(s));
+    let total_len_in_bits = midstate.blocks as u64 * 512 + msg_len_in_bits;
+    let total_len = [
+        builder.constant_u32((total_len_in_bits >> 32) as u32),
+        builder.constant_u32(total_len_in_bits as u32),
+    ];
+    let (message, digest) = make_suffix_circuits(builder, state, total_len, msg_len_in_bits);
+    Sha256Targets {
+        message,
+        digest: digest_to_bits(builder, &digest),
+    }
+}
+
+/// Like `make_circuits_with_midstate`, but the midstate is a public input, so
+/// one circuit serves any block-aligned prefix. Set it with
+/// `fill_midstate_targets`.
+pub fn make_circuits_with_public_midstate<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+) -> Sha256MidstateTargets {
+    let state: [U32Target; 8] = core::array::from_fn(|_| builder.add_virtual_u32_target());
+    let blocks = builder.add_virtual_u32_target();
+    builder.range_check_u32(state.to_vec());
+    for s in &state {
+        builder.register_public_input(s.0);
+    }
+    builder.register_public_input(blocks.0);
+
+    // total = 512 * blocks + msg_len. The words are witnesses: both are range
+    // checked and `lo + 2^32 * hi` must equal `total`. That sum is below 2^64,
+    // so besides `total` itself it can only hit `total + p`, which needs
+    // `hi == u32::MAX`; ruling that out makes the split canonical.
+    let max_total = 512 * u32::MAX as u64 + msg_len_in_bits;
+    assert!(
+        max_total >> 32 < u32::MAX as u64,
+        "suffix of {msg_len_in_bits} bits is too long"
+    );
+    let total_len = [
+        builder.add_virtual_u32_target(),
+        builder.add_virtual_u32_target(),
+    ];
+    let [hi, lo] = total_len;
+    builder.range_check_u32(vec![blocks, hi, lo]);
+    let msg_len = builder.constant(F::from_canonical_u64(msg_len_in_bits));
+    let total = builder.mul_const_add(F::from_canonical_u64(512), blocks.0, msg_len);
+    let split = builder.mul_const_add(F::from_canonical_u64(1 << 32), hi.0, lo.0);
+    builder.connect(total, split);
+    let hi_max = builder.constant(F::from_canonical_u32(u32::MAX));
+    let hi_is_max = builder.is_equal(hi.0, hi_max);
+    builder.assert_zero(hi_is_max.target);
+
+    let (message, digest) = make_suffix_circuits(builder, state, total_len, msg_len_in_bits);
+    Sha256MidstateTargets {
+        message,
+        digest: digest_to_bits(builder, &digest),
+        state,
+        blocks,
+        total_len,
+        msg_len_in_bits,
+    }
+}
+
+pub fn fill_midstate_targets<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    targets: &Sha256MidstateTargets,
+    midstate: &Sha256Midstate,
+) -> Result<()> {
+    for (&target, &value) in targets.state.iter().zip(&midstate.state) {
+        pw.set_u32_target(target, value)?;
+    }
+    pw.set_u32_target(targets.blocks, midstate.blocks)?;
+    let total = midstate.blocks as u64 * 512 + targets.msg_len_in_bits;
+    pw.set_u32_target(targets.total_len[0], (total >> 32) as u32)?;
+    pw.set_u32_target(targets.total_len[1], total as u32)
+}
+
+pub struct VariableLengthSha256Targets {
+    /// Padded message bits, constrained to be boolean by `message_words`.
+    pub message: Vec<BoolTarget>,
+    pub digest: Vec<BoolTarget>,
+    pub msg_len: U32Target,
+    pub msg_blocks: U32Target,
+}
+
+/// Like `VariableLengthSha256Targets`, with the digest as eight big-endian
+/// words.
+pub struct VariableLengthSha256U32Targets {
+    /// Padded message bits, constrained to be boolean by `message_words`.
+    pub message: Vec<BoolTarget>,
+    pub digest: [U32Target; 8],
+    pub msg_len: U32Target,
+    pub msg_blocks: U32Target,
+}
+
+/// Constrains `msg_blocks` to `ceil((msg_len + 65) / 512)`, given that it is
+/// otherwise bounded:
+/// `msg_len + 64 = 512 * (msg_blocks - 1) + r` with `0 <= r < 512`.
+pub(crate) fn constrain_msg_blocks<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len: Target,
+    msg_blocks: Target,
+) {
+    let len_plus_576 = builder.add_const(msg_len, F::from_canonical_u64(576));
+    let r = builder.mul_const_add(-F::from_canonical_u64(512), msg_blocks, len_plus_576);
+    builder.range_check(r, 9);
+}
+
+/// Constrains the 512-bit `block` starting at bit `block_start` of a message
+/// padded for `msg_len` bits: from position `msg_len` on, every bit must be the
+/// `1` bit, zero fill, or (if `is_last`) the big-endian length, whose low
+/// `len_bits.len()` bits are `len_bits`. `is_pad` must be
+/// `[block_start > msg_len]`; the returned value is the same flag for the next
+/// block.
+pub(crate) fn constrain_padding_block<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    block: &[BoolTarget],
+    block_start: Target,
+    msg_len: Target,
+    len_bits: &[BoolTarget],
+    is_last: BoolTarget,
+    mut is_pad: Target,
+) -> Target {
+    assert_eq!(block.len(), 512);
+    assert!(len_bits.len() <= 64);
+    let len_start = 512 - len_bits.len();
+    for (offset, bit) in block.iter().enumerate() {
+        let pos = builder.add_const(block_start, F::from_canonical_usize(offset));
+        let is_one_bit = builder.is_equal(msg_len, pos);
+        is_pad = builder.add(is_pad, is_one_bit.target);
+
+        // is_pad * bit - [pos == msg_len] - is_last * length_bit = 0
+        let mut diff = builder.mul_sub(is_pad, bit.target, is_one_bit.target);
+        if offset >= len_start {
+            let len_bit = len_bits[offset - len_start];
+            diff = builder.arithmetic(F::NEG_ONE, F::ONE, is_last.target, len_bit.target, diff);
+        }
+        builder.assert_zero(diff);
+    }
+    is_pad
+}
+
+/// Constrains the padded message against `msg_len` (in bits) and `msg_blocks`:
+/// `msg_blocks` must be `ceil((msg_len + 65) / 512)`, and every bit from
+/// position `msg_len` on must be the `1` bit, zero fill, or the big-endian
+/// length in the last 64 bits of block `msg_blocks - 1`. Bits after the last
+/// block must be zero. The encoded length is `prefix_len_in_bits + msg_len`,
+/// for a message that continues a block-aligned prefix.
+fn constrain_variable_length_padding<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    message: &[BoolTarget],
+    msg_len: U32Target,
+    msg_blocks: U32Target,
+    prefix_len_in_bits: u64,
+) {
+    let tot_blocks = message.len() / 512;
+
+    // last_block[b] is set iff block b is the last one; exactly one must be set,
+    // which also bounds msg_blocks to 1..=tot_blocks.
+    let mut last_block = Vec::new();
+    for blk in 0..tot_blocks {
+        let blk_count = builder.constant(F::from_canonical_usize(blk + 1));
+        last_block.push(builder.is_equal(msg_blocks.0, blk_count));
+    }
+    let num_last_blocks = builder.add_many(last_block.iter().map(|b| b.target));
+    builder.assert_one(num_last_blocks);
+    constrain_msg_blocks(builder, msg_len.0, msg_blocks.0);
+
+    // Also range checks msg_len to 32 bits, so the upper length word is zero.
+    let mut len_bits = u32_to_bits_target::<F, D, 2>(builder, &msg_len);
+    if prefix_len_in_bits > 0 {
+        let total_len = builder.add_const(msg_len.0, F::from_canonical_u64(prefix_len_in_bits));
+        len_bits = u32_to_bits_target::<F, D, 2>(builder, &U32Target(total_len));
+    }
+
+    // is_pad = [i >= msg_len], accumulated from the one-hot [i == msg_len].
+    let mut is_pad = builder.zero();
+    for (blk, block) in message.chunks(512).enumerate() {
+        let block_start = builder.constant(F::from_canonical_usize(blk * 512));
+        is_pad = constrain_padding_block(
+            builder,
+            block,
+            block_start,
+            msg_len.0,
+            &len_bits,
+            last_block[blk],
+            is_pad,
+        );
+    }
+}
+
+/// Builds a circuit hashing a padded message of up to `max_total_bits` bits
+/// with initial state `iv`, so it serves SHA-256 and SHA-224 alike. The
+/// padding is checked in-circuit against `msg_len` and `msg_blocks`, so the
+/// digest covers the first `msg_len` message bits.
+fn make_variable_length_circuits_with_iv<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    max_total_bits: usize,
+    iv: &[u32; 8],
+) -> VariableLengthSha256U32Targets {
+    let state = iv.map(|c| builder.constant_u32(c));
+    make_variable_length_suffix_circuits(builder, state, 0, max_total_bits)
+}
+
+/// Like `make_variable_length_circuits_with_iv`, for a message that continues
+/// a prefix of `prefix_blocks` blocks hashed into `state`. `msg_len` and
+/// `msg_blocks` count the suffix only; the encoded length includes the prefix.
+pub(crate) fn make_variable_length_suffix_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    state: [U32Target; 8],
+    prefix_blocks: u64,
+    max_total_bits: usize,
+) -> VariableLengthSha256U32Targets {
+    assert!(
+        max_total_bits % 512 == 0,
+        "max_total_bits must be a multiple of 512 (got {})",
+        max_total_bits
+    );
+    let tot_blocks = max_total_bits / 512;
+    let mut message = Vec::new();
+
+    let msg_len = builder.add_virtual_u32_target();
+    let msg_blocks = builder.add_virtual_u32_target();
+
+    for _ in 0..max_total_bits {
+        message.push(builder.add_virtual_bool_target_unsafe());
+    }
+    let words = message_words(builder, &message);
+    constrain_variable_length_padding(builder, &message, msg_len, msg_blocks, prefix_blocks * 512);
+
+    // init states with lazy evaluation
+    let mut state: Vec<_> = state
+        .iter()
+        .map(|&s| LazyU32WithBits::from_u32(s))
+        .collect();
+
+    let mut do_block = builder.constant_bool(true);
+    for blk in 0..tot_blocks {
+        let blk_target = builder.constant_u32(blk as u32);
+
+        let after_msg_block = builder.is_equal(blk_target.0, msg_blocks.0);
+        let not_after_msg_block = builder.not(after_msg_block);
+        do_block = builder.and(do_block, not_after_msg_block);
+
+        let new_state = compress_lazy(builder, &state, &words[blk * 16..blk * 16 + 16]);
+        for i in 0..8 {
+            // Use select to conditionally update state based on do_block
+            let new_u32 = new_state[i].get_u32(builder);
+            let old_u32 = state[i].get_u32(builder);
+            let new_u32 = builder.select(do_block, new_u32.0, old_u32.0);
+            state[i] = LazyU32WithBits::from_u32(U32Target(new_u32));
+        }
+    }
+
+    VariableLengthSha256U32Targets {
+        message,
+        digest: core::array::from_fn(|i| state[i].get_u32(builder)),
+        msg_len,
+        msg_blocks,
+    }
+}
+
+/// Builds a SHA-256 circuit for messages of up to `max_total_bits` padded bits,
+/// returning the digest as eight words; the padding is checked against
+/// `msg_len` and `msg_blocks`.
+pub fn make_variable_length_circuits_u32<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    max_total_bits: usize,
+) -> VariableLengthSha256U32Targets {
+    make_variable_length_circuits_with_iv(builder, max_total_bits, &H256)
+}
+
+/// Like `make_variable_length_circuits_u32`, with the digest decomposed into
+/// 256 bits.
+pub fn make_variable_length_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    max_total_bits: usize,
+) -> VariableLengthSha256Targets {
+    let targets = make_variable_length_circuits_u32(builder, max_total_bits);
+    VariableLengthSha256Targets {
+        message: targets.message,
+        digest: digest_to_bits(builder, &targets.digest),
+        msg_len: targets.msg_len,
+        msg_blocks: targets.msg_blocks,
+    }
+}
+
+/// SHA-224 counterpart of `make_variable_length_circuits`; the digest holds
+/// 224 bits. The padding is the same, so `fill_variable_length_circuits`
+/// fills it too.
+pub fn make_variable_length_sha224_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    max_total_bits: usize,
+) -> VariableLengthSha256Targets {
+    let targets = make_variable_length_circuits_with_iv(builder, max_total_bits, &H224);
+    VariableLengthSha256Targets {
+        message: targets.message,
+        digest: digest_to_bits(builder, &targets.digest[..7]),
+        msg_len: targets.msg_len,
+        msg_blocks: targets.msg_blocks,
+    }
+}
+
+/// Returns the SHA-256 padded bits of `msg`, zero-extended to `max_total_bits`.
+pub fn variable_length_padded_bits(msg: &[u8], max_total_bits: usize) -> Vec<bool> {
+    suffix_padded_bits(msg, 0, max_total_bits)
+}
+
+/// Like `variable_length_padded_bits`, for a message that continues a
+/// block-aligned prefix of `prefix_len_in_bits` bits: the encoded length
+/// counts the prefix too.
+pub(crate) fn suffix_padded_bits(
+    msg: &[u8],
+    prefix_len_in_bits: usize,
+    max_total_bits: usize,
+) -> Vec<bool> {
+    assert_eq!(prefix_len_in_bits % 512, 0);
+    let msg_bits = array_to_bits(msg);
+    let msg_blocks = (msg_bits.len() + 65 + 511) / 512;
+    let msg_bits_len = msg_bits.len();
+
+    assert!(
+        max_total_bits >= msg_blocks * 512,
+        "Message too long: needs {} bits but circuit only supports {} bits",
+        msg_blocks * 512,
+        max_total_bits
+    );
+
+    (0..max_total_bits)
+        .map(|i| {
+            if i < msg_bits_len {
+                msg_bits[i]
+            } else if i == msg_bits_len {
+                true // the mandatory `1` bit
+            } else if i >= msg_blocks * 512 - 64 && i < msg_blocks * 512 {
+                // length encoding, big-endian
+                (((prefix_len_in_bits + msg_bits_len) >> (msg_blocks * 512 - i - 1)) & 1) == 1
+            } else {
+                false
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn fill_variable_length_message<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    msg: &[u8],
+    prefix_len_in_bits: usize,
+    max_total_bits: usize,
+    message: &[BoolTarget],
+    msg_len: U32Target,
+    msg_blocks: U32Target,
+) -> Result<()> {
+    assert!(
+        max_total_bits % 512 == 0,
+        "max_total_bits must be a multiple of 512 (got {})",
+        max_total_bits
+    );
+    assert_eq!(
+        max_total_bits,
+        message.len(),
+        "max_total_bits ({}) must match message target length ({})",
+        max_total_bits,
+        message.len()
+    );
+
+    let msg_bits_len = msg.len() * 8;
+    let num_blocks = (msg_bits_len + 65).div_ceil(512);
+    let padded = suffix_padded_bits(msg, prefix_len_in_bits, max_total_bits);
+
+    pw.set_u32_target(msg_len, msg_bits_len as u32)?;
+    pw.set_u32_target(msg_blocks, num_blocks as u32)?;
+
+    for (target, bit) in message.iter().zip(padded) {
+        pw.set_bool_target(*target, bit)?;
+    }
+    Ok(())
+}
+
+pub fn fill_variable_length_circuits<F: RichField + Extendable<D>, const D: usize>(
+    pw: &mut PartialWitness<F>,
+    msg: &[u8],
+    max_total_bits: usize,
+    targets: &VariableLengthSha256Targets,
+) -> Result<()> {
+    fill_variable_length_message(
+        pw,
+        msg,
+        0,
+        max_total_bits,
+        &targets.message,
+        targets.msg_len,
+        targets.msg_blocks,
+    )
+}
+
+pub fn fill_variable_length_circuits_u32<F: RichField + Extendable<D>, const D: usize>(
+    pw: &mut PartialWitness<F>,
+    msg: &[u8],
+    max_total_bits: usize,
+    targets: &VariableLengthSha256U32Targets,
+) -> Result<()> {
+    fill_variable_length_message(
+        pw,
+        msg,
+        0,
+        max_total_bits,
+        &targets.message,
+        targets.msg_len,
+        targets.msg_blocks,
+    )
+}
+
+/// Appends SHA-256 padding to big-endian message words holding `msg_len_bytes`
+/// bytes. The unused low bytes of a partial last word must be zero.
+fn pad_words<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    words: &[U32Target],
+    msg_len_bytes: usize,
+) -> Vec<U32Target> {
+    let mut padded = words.to_vec();
+    let rem = msg_len_bytes % 4;
+    if rem == 0 {
+        padded.push(builder.constant_u32(0x80000000));
+    } else {
+        let last = padded.pop().unwrap();
+        let one_bit = F::from_canonical_u32(0x80 << (8 * (3 - rem)));
+        padded.push(U32Target(builder.add_const(last.0, one_bit)));
+    }
+    while padded.len() % 16 != 14 {
+        padded.push(builder.zero_u32());
+    }
+    let msg_len_in_bits = msg_len_bytes as u64 * 8;
+    padded.push(builder.constant_u32((msg_len_in_bits >> 32) as u32));
+    padded.push(builder.constant_u32(msg_len_in_bits as u32));
+    padded
+}
+
+/// Hashes already padded big-endian words from the initial state `iv`,
+/// returning the final state.
+fn sha256_padded_words<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    iv: &[u32; 8],
+    words: &[U32Target],
+) -> [U32Target; 8] {
+    sha256_padded_words_with(builder, &mut BitRoundFunctions, iv, words)
+}
+
+fn sha256_padded_words_with<F, const D: usize, R>(
+    builder: &mut CircuitBuilder<F, D>,
+    round_functions: &mut R,
+    iv: &[u32; 8],
+    words: &[U32Target],
+) -> [U32Target; 8]
+where
+    F: RichField + Extendable<D>,
+    R: Sha256RoundFunctions<F, D>,
+{
+    assert_eq!(words.len() % 16, 0);
+    let mut state = Vec::new();
+    for c in iv {
+        let u32_target = builder.constant_u32(*c);
+        state.push(round_functions.u32_to_word(builder, u32_target));
+    }
+    for block in words.chunks(16) {
+        state = compress_with(builder, round_functions, &state, block);
+    }
+    core::array::from_fn(|i| round_functions.word_to_u32(builder, &state[i]))
+}
+
+/// Byte- and word-level SHA-256 gadgets. Digests are returned as eight
+/// big-endian words.
+pub trait CircuitBuilderSha256<F: RichField + Extendable<D>, const D: usize> {
+    /// Adds a virtual target range-checked to `0..256`.
+    fn add_virtual_byte_target(&mut self) -> Target;
+
+    fn add_virtual_byte_targets(&mut self, n: usize) -> Vec<Target>;
+
+    /// SHA-256 of `msg`, whose targets must already be range-checked to bytes
+    /// (e.g. allocated with `add_virtual_byte_target`).
+    fn sha256_bytes(&mut self, msg: &[Target]) -> [U32Target; 8];
+
+    /// SHA-256 of the first `msg_len_bytes` bytes of `msg`, given as big-endian
+    /// words that must already be range-checked to 32 bits. The unused low
+    /// bytes of a partial last word are constrained to be zero.
+    fn sha256_u32(&mut self, msg: &[U32Target], msg_len_bytes: usize) -> [U32Target; 8];
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilderSha256<F, D>
+    for CircuitBuilder<F, D>
+{
+    fn add_virtual_byte_target(&mut self) -> Target {
+        let byte = self.add_virtual_target();
+        self.split_le_base_optimized::<2>(byte, 8);
+        byte
+    }
+
+    fn add_virtual_byte_targets(&mut self, n: usize) -> Vec<Target> {
+        (0..n).map(|_| self.add_virtual_byte_target()).collect()
+    }
+
+    fn sha256_bytes(&mut self, msg: &[Target]) -> [U32Target; 8] {
+        let base = F::from_canonical_u32(256);
+        let mut words = Vec::new();
+        for chunk in msg.chunks(4) {
+            let mut word = chunk[0];
+            for &byte in &chunk[1..] {
+                word = self.mul_const_add(base, word, byte);
+            }
+            // Left-align a partial last word.
+            if chunk.len() < 4 {
+                let shift = F::from_canonical_u64(1 << (8 * (4 - chunk.len())));
+                word = self.mul_const(shift, word);
+            }
+            words.push(U32Target(word));
+        }
+        let padded = pad_words(self, &words, msg.len());
+        sha256_padded_words(self, &H256, &padded)
+    }
+
+    fn sha256_u32(&mut self, msg: &[U32Target], msg_len_bytes: usize) -> [U32Target; 8] {
+        assert_eq!(
+            msg.len(),
+            msg_len_bytes.div_ceil(4),
+            "expected {} words for {} bytes",
+            msg_len_bytes.div_ceil(4),
+            msg_len_bytes
+        );
+        let rem = msg_len_bytes % 4;
+        if rem != 0 {
+            let last = msg[msg.len() - 1];
+            let bits = self.split_le_base_optimized::<2>(last.0, 32);
+            for &bit in &bits[..8 * (4 - rem)] {
+                self.assert_zero(bit);
+            }
+        }
+        let padded = pad_words(self, msg, msg_len_bytes);
+        sha256_padded_words(self, &H256, &padded)
+    }
+}
+
+/// Sets byte targets created for `CircuitBuilderSha256::sha256_bytes`.
+pub fn fill_sha256_bytes<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    targets: &[Target],
+    msg: &[u8],
+) -> Result<()> {
+    assert_eq!(targets.len(), msg.len());
+    for (&target, &byte) in targets.iter().zip(msg) {
+        pw.set_target(target, F::from_canonical_u8(byte))?;
+    }
+    Ok(())
+}
+
+/// Sets word targets created for `CircuitBuilderSha256::sha256_u32`, packing
+/// `msg` big-endian and zero-filling a partial last word.
+pub fn fill_sha256_u32<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    targets: &[U32Target],
+    msg: &[u8],
+) -> Result<()> {
+    assert_eq!(targets.len(), msg.len().div_ceil(4));
+    for (&target, chunk) in targets.iter().zip(msg.chunks(4)) {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        pw.set_u32_target(target, u32::from_be_bytes(word))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use plonky2::{
+        field::{extension::Extendable, types::Field},
+        hash::hash_types::RichField,
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::{CircuitConfig, CircuitData},
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+    use plonky2_u32::{gadgets::arithmetic_u32::CircuitBuilderU32, witness::WitnessU32};
+    use sha2::Digest;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use crate::circuit::{
+        array_to_bits, digest_from_proof, digest_to_bits, digest_to_hash_out,
+        digest_to_hash_out_native, fill_midstate_targets, fill_sha256_bytes, fill_sha256_u32,
+        fill_variable_length_circuits, fixed_length_message, make_circuits, make_circuits_u32,
+        make_circuits_u32_with_backend, make_circuits_with_midstate,
+        make_circuits_with_public_midstate, make_public_circuits, make_sha224_circuits,
+        make_variable_length_circuits, make_variable_length_sha224_circuits, public_inputs_native,
+        sha256_compress, sha256_compress_native, sha256_padded_words_with,
+        variable_length_padded_bits, BitRoundFunctions, CircuitBuilderSha256, PublicMessage,
+        Sha256Backend, Sha256Midstate, Sha256RoundFunctions, EXAMPLE_MESSAGE, H256,
+    };
+    use crate::gadgets::XorOps;
+    use crate::lazy::LazyU32WithBits;
+    use crate::lookup::Shift;
+    use plonky2_u32::gadgets::arithmetic_u32::U32Target;
+
+    #[test]
+    fn test_sha256_circuit() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::wide_ecc_config());
+
+        let msg = EXAMPLE_MESSAGE;
+
+        let digest = sha2::Sha256::digest(&msg);
+        let msg_len_in_bits = (msg.len() * 8) as usize;
+
+        // Create SHA256 circuit using your make_circuits function
+        let sha256_targets = make_circuits(&mut builder, msg_len_in_bits as u64);
+
+        // Create witness
+        let mut pw = PartialWitness::new();
+
+        // Convert message bytes to bits and set as witness
+        let msg_bits = array_to_bits(&msg);
+        for (i, &bit) in msg_bits.iter().enumerate().take(msg_len_in_bits as usize) {
+            pw.set_bool_target(sha256_targets.message[i], bit)?;
+        }
+
+        // Convert expected digest bytes to bits and set as witness
+        let expected_digest_bits = array_to_bits(&digest);
+        for (i, expected_digest_bit) in expected_digest_bits.iter().enumerate() {
+            if *expected_digest_bit {
+                builder.assert_one(sha256_targets.digest[i].target);
+            } else {
+                builder.assert_zero(sha256_targets.digest[i].target);
+            }
+        }
+
+        println!(
+            "Constructing inner proof with {} gates",
+            builder.num_gates()
+        );
+
+        // Build the circuit
+        let data = builder.build::<C>();
+
+        // Generate proof
+        let proof = data.prove(pw)?;
+
+        // Verify proof
+        data.verify(proof)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sha256_circuit_short() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let msg = [
+            132, 106, 83, 105, 103, 110, 97, 116, 117, 114, 101, 49, 67, 161, 1, 38, 64, 89, 11,
+            59, 216, 24, 89, 11, 54, 166, 103, 118, 101, 114, 115, 105, 111, 110, 99, 49, 46, 48,
+            111, 100, 105, 103, 101, 115, 116, 65, 108, 103, 111, 114, 105, 116, 104, 109, 103, 83,
+            72, 65, 45, 50, 53, 54, 108, 118, 97, 108, 117, 101, 68, 105, 103, 101, 115, 116, 115,
+            162, 113, 111, 114, 103, 46, 105, 115, 111, 46, 49, 56, 48, 49, 51, 46, 53, 46, 49,
+            184, 37, 26, 0, 29, 90, 114, 88, 32, 0, 23, 37,
+        ];
+        let digest = sha2::Sha256::digest(&msg);
+        let msg_len_in_bits = (msg.len() * 8) as usize;
+
+        // Create SHA256 circuit using your make_circuits function
+        let sha256_targets = make_circuits(&mut builder, msg_len_in_bits as u64);
+
+        // Create witness
+        let mut pw = PartialWitness::new();
+
+        // Convert message bytes to bits and set as witness
+        let msg_bits = array_to_bits(&msg);
+        for (i, &bit) in msg_bits.iter().enumerate().take(msg_len_in_bits as usize) {
+            pw.set_bool_target(sha256_targets.message[i], bit)?;
+        }
+
+        // Convert expected digest bytes to bits and set as witness
+        let expected_digest_bits = array_to_bits(&digest);
+        for (i, expected_digest_bit) in expected_digest_bits.iter().enumerate() {
+            if *expected_digest_bit {
+                builder.assert_one(sha256_targets.digest[i].target);
+            } else {
+                builder.assert_zero(sha256_targets.digest[i].target);
+            }
+        }
+
+        println!(
+            "Constructing inner proof with {} gates",
+            builder.num_gates()
+        );
+
+        // Build the circuit
+        let data = builder.build::<C>();
+
+        // Generate proof
+        let proof = data.prove(pw)?;
+
+        // Verify proof
+        data.verify(proof)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_variable_length_sha256_circuit() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::wide_ecc_config());
+
+        let msg = EXAMPLE_MESSAGE;
+
+        let digest = sha2::Sha256::digest(&msg);
+
+        let tot_bits = 512 * 48; // 48 blocks, instead of 47 blocks which is needed
+
+        // Create SHA256 circuit using your make_circuits function
+        let sha256_targets = make_variable_length_circuits(&mut builder, tot_bits);
+
+        // Create witness
+        let mut pw = PartialWitness::new();
+
+        fill_variable_length_circuits::<F, D>(&mut pw, &msg, tot_bits, &sha256_targets)?;
+
+        // Convert expected digest bytes to bits and set as witness
+        let expected_digest_bits = array_to_bits(&digest);
+        for (i, expected_digest_bit) in expected_digest_bits.iter().enumerate() {
             if *expected_digest_bit {
                 builder.assert_one(sha256_targets.digest[i].target);
             } else {
@@ -696,102 +1817,412 @@ pub mod tests {
             }
         }
 
-        println!(
-            "Constructing inner proof with {} gates",
-            builder.num_gates()
-        );
+        println!(
+            "Constructing inner proof with {} gates",
+            builder.num_gates()
+        );
+
+        // Build the circuit
+        let data = builder.build::<C>();
+
+        // Generate proof
+        let proof = data.prove(pw)?;
+
+        // Verify proof
+        data.verify(proof)?;
+
+        Ok(())
+    }
+    /// Tries to prove with `pw` and reports whether a valid proof came out.
+    fn proof_accepted<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+        data: &CircuitData<F, C, D>,
+        pw: PartialWitness<F>,
+    ) -> bool {
+        // A violated constraint may make the prover panic rather than error out.
+        match catch_unwind(AssertUnwindSafe(|| data.prove(pw))) {
+            Ok(Ok(proof)) => data.verify(proof).is_ok(),
+            _ => false,
+        }
+    }
+
+    /// Proves the 2-block variable-length circuit over `msg` with the given
+    /// (possibly tampered) padded bits and block count, and reports whether
+    /// a valid proof came out.
+    fn variable_length_padding_accepted(
+        msg: &[u8],
+        padded: &[bool],
+        msg_blocks: u32,
+    ) -> anyhow::Result<bool> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let tot_bits = 512 * 2;
+        let sha256_targets = make_variable_length_circuits(&mut builder, tot_bits);
+
+        let mut pw = PartialWitness::new();
+        pw.set_u32_target(sha256_targets.msg_len, (msg.len() * 8) as u32)?;
+        pw.set_u32_target(sha256_targets.msg_blocks, msg_blocks)?;
+        for (target, bit) in sha256_targets.message.iter().zip(padded) {
+            pw.set_bool_target(*target, *bit)?;
+        }
+
+        let data = builder.build::<C>();
+        Ok(proof_accepted(&data, pw))
+    }
+
+    #[test]
+    fn test_variable_length_sha256_padding() -> anyhow::Result<()> {
+        let msg = b"abc";
+        let padded = variable_length_padded_bits(msg, 1024);
+        assert!(variable_length_padding_accepted(msg, &padded, 1)?);
+
+        // Data hidden after the `1` bit.
+        let mut tampered = padded.clone();
+        tampered[100] = true;
+        assert!(!variable_length_padding_accepted(msg, &tampered, 1)?);
+
+        // Missing `1` bit.
+        let mut tampered = padded.clone();
+        tampered[24] = false;
+        assert!(!variable_length_padding_accepted(msg, &tampered, 1)?);
+
+        // Wrong length encoding.
+        let mut tampered = padded.clone();
+        tampered[511] = true;
+        assert!(!variable_length_padding_accepted(msg, &tampered, 1)?);
+
+        // Length encoded in the second block while claiming two blocks.
+        let mut tampered = padded.clone();
+        tampered[448..512].iter_mut().for_each(|b| *b = false);
+        tampered[1024 - 64..].copy_from_slice(&padded[448..512]);
+        assert!(!variable_length_padding_accepted(msg, &tampered, 2)?);
+
+        Ok(())
+    }
+    #[test]
+    fn test_sha256_circuit_rejects_non_boolean_message() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let msg = b"abc";
+        let sha256_targets = make_circuits(&mut builder, (msg.len() * 8) as u64);
+
+        // The first bit of `a` is 0; claim it is 2 instead.
+        let mut pw = PartialWitness::new();
+        let msg_bits = array_to_bits(msg);
+        pw.set_target(sha256_targets.message[0].target, F::TWO)?;
+        for (i, &bit) in msg_bits.iter().enumerate().skip(1) {
+            pw.set_bool_target(sha256_targets.message[i], bit)?;
+        }
+
+        let data = builder.build::<C>();
+        assert!(!proof_accepted(&data, pw));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_variable_length_sha256_rejects_non_boolean_message() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let msg = b"abc";
+        let tot_bits = 512;
+        let sha256_targets = make_variable_length_circuits(&mut builder, tot_bits);
+
+        let mut pw = PartialWitness::new();
+        let padded = variable_length_padded_bits(msg, tot_bits);
+        pw.set_u32_target(sha256_targets.msg_len, (msg.len() * 8) as u32)?;
+        pw.set_u32_target(sha256_targets.msg_blocks, 1)?;
+        pw.set_target(sha256_targets.message[0].target, F::TWO)?;
+        for (i, &bit) in padded.iter().enumerate().skip(1) {
+            pw.set_bool_target(sha256_targets.message[i], bit)?;
+        }
+
+        let data = builder.build::<C>();
+        assert!(!proof_accepted(&data, pw));
+
+        Ok(())
+    }
+    #[test]
+    fn test_sha256_bytes() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        // Two blocks, with a partial last word.
+        let msg = &EXAMPLE_MESSAGE[..70];
+        let digest = sha2::Sha256::digest(msg);
+
+        let msg_targets = builder.add_virtual_byte_targets(msg.len());
+        let digest_targets = builder.sha256_bytes(&msg_targets);
+        for (word, expected) in digest_targets.iter().zip(digest.chunks(4)) {
+            let expected = builder.constant_u32(u32::from_be_bytes(expected.try_into()?));
+            builder.connect_u32(*word, expected);
+        }
+
+        let mut pw = PartialWitness::new();
+        fill_sha256_bytes(&mut pw, &msg_targets, msg)?;
 
-        // Build the circuit
         let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
 
-        // Generate proof
+    #[test]
+    fn test_sha256_u32() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let msg = b"abc";
+        let digest = sha2::Sha256::digest(msg);
+
+        let msg_targets = builder.add_virtual_u32_targets(msg.len().div_ceil(4));
+        builder.range_check_u32(msg_targets.clone());
+        let digest_targets = builder.sha256_u32(&msg_targets, msg.len());
+        for (word, expected) in digest_targets.iter().zip(digest.chunks(4)) {
+            let expected = builder.constant_u32(u32::from_be_bytes(expected.try_into()?));
+            builder.connect_u32(*word, expected);
+        }
+
+        let mut pw = PartialWitness::new();
+        fill_sha256_u32(&mut pw, &msg_targets, msg)?;
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+    #[test]
+    fn test_sha256_circuit_u32_digest() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let msg = &EXAMPLE_MESSAGE[..100];
+        let digest = sha2::Sha256::digest(msg);
+        let digest_words: [u32; 8] = core::array::from_fn(|i| {
+            u32::from_be_bytes(digest[4 * i..4 * i + 4].try_into().unwrap())
+        });
+
+        let sha256_targets = make_circuits_u32(&mut builder, (msg.len() * 8) as u64);
+        for (word, &expected) in sha256_targets.digest.iter().zip(&digest_words) {
+            let expected = builder.constant_u32(expected);
+            builder.connect_u32(*word, expected);
+        }
+        let hash_out = digest_to_hash_out(&mut builder, &sha256_targets.digest);
+        let expected = builder.constant_hash(digest_to_hash_out_native(&digest_words));
+        builder.connect_hashes(hash_out, expected);
+
+        let mut pw = PartialWitness::new();
+        for (target, bit) in sha256_targets.message.iter().zip(array_to_bits(msg)) {
+            pw.set_bool_target(*target, bit)?;
+        }
+
+        let data = builder.build::<C>();
         let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
 
-        // Verify proof
-        data.verify(proof)?;
+    #[test]
+    fn test_digest_to_hash_out_rejects_wrapping_words() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let digest: [U32Target; 8] = core::array::from_fn(|_| builder.add_virtual_u32_target());
+        builder.range_check_u32(digest.to_vec());
+        let hash_out = digest_to_hash_out(&mut builder, &digest);
+        builder.register_public_inputs(&hash_out.elements);
+        let data = builder.build::<C>();
 
+        // (0xffffffff, 1) would pack to the same element as (0, 0).
+        let mut pw = PartialWitness::new();
+        for (i, &target) in digest.iter().enumerate() {
+            let value = if i == 0 { u32::MAX } else { 1 };
+            pw.set_u32_target(target, value)?;
+        }
+        assert!(data.prove(pw).is_err());
         Ok(())
     }
 
     #[test]
-    fn test_sha256_circuit_short() -> anyhow::Result<()> {
+    fn test_sha224_circuit() -> anyhow::Result<()> {
         const D: usize = 2;
         type C = PoseidonGoldilocksConfig;
         type F = <C as GenericConfig<D>>::F;
         let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
 
-        let msg = [
-            132, 106, 83, 105, 103, 110, 97, 116, 117, 114, 101, 49, 67, 161, 1, 38, 64, 89, 11,
-            59, 216, 24, 89, 11, 54, 166, 103, 118, 101, 114, 115, 105, 111, 110, 99, 49, 46, 48,
-            111, 100, 105, 103, 101, 115, 116, 65, 108, 103, 111, 114, 105, 116, 104, 109, 103, 83,
-            72, 65, 45, 50, 53, 54, 108, 118, 97, 108, 117, 101, 68, 105, 103, 101, 115, 116, 115,
-            162, 113, 111, 114, 103, 46, 105, 115, 111, 46, 49, 56, 48, 49, 51, 46, 53, 46, 49,
-            184, 37, 26, 0, 29, 90, 114, 88, 32, 0, 23, 37,
-        ];
-        let digest = sha2::Sha256::digest(&msg);
-        let msg_len_in_bits = (msg.len() * 8) as usize;
+        let msg = &EXAMPLE_MESSAGE[..100];
+        let digest = sha2::Sha224::digest(msg);
+        let msg_len_in_bits = (msg.len() * 8) as u64;
 
-        // Create SHA256 circuit using your make_circuits function
-        let sha256_targets = make_circuits(&mut builder, msg_len_in_bits as u64);
+        let sha224_targets = make_sha224_circuits(&mut builder, msg_len_in_bits);
+        assert_eq!(sha224_targets.digest.len(), 224);
 
-        // Create witness
         let mut pw = PartialWitness::new();
-
-        // Convert message bytes to bits and set as witness
-        let msg_bits = array_to_bits(&msg);
-        for (i, &bit) in msg_bits.iter().enumerate().take(msg_len_in_bits as usize) {
-            pw.set_bool_target(sha256_targets.message[i], bit)?;
+        for (target, bit) in sha224_targets.message.iter().zip(array_to_bits(msg)) {
+            pw.set_bool_target(*target, bit)?;
         }
 
-        // Convert expected digest bytes to bits and set as witness
         let expected_digest_bits = array_to_bits(&digest);
         for (i, expected_digest_bit) in expected_digest_bits.iter().enumerate() {
             if *expected_digest_bit {
-                builder.assert_one(sha256_targets.digest[i].target);
+                builder.assert_one(sha224_targets.digest[i].target);
             } else {
-                builder.assert_zero(sha256_targets.digest[i].target);
+                builder.assert_zero(sha224_targets.digest[i].target);
             }
         }
 
-        println!(
-            "Constructing inner proof with {} gates",
-            builder.num_gates()
-        );
-
-        // Build the circuit
         let data = builder.build::<C>();
-
-        // Generate proof
         let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
 
-        // Verify proof
-        data.verify(proof)?;
+    #[test]
+    fn test_variable_length_sha224_circuit() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
 
-        Ok(())
+        let msg = &EXAMPLE_MESSAGE[..100];
+        let digest = sha2::Sha224::digest(msg);
+        let tot_bits = 512 * 3;
+
+        let sha224_targets = make_variable_length_sha224_circuits(&mut builder, tot_bits);
+        assert_eq!(sha224_targets.digest.len(), 224);
+
+        let mut pw = PartialWitness::new();
+        fill_variable_length_circuits::<F, D>(&mut pw, msg, tot_bits, &sha224_targets)?;
+
+        let expected_digest_bits = array_to_bits(&digest);
+        for (i, expected_digest_bit) in expected_digest_bits.iter().enumerate() {
+            if *expected_digest_bit {
+                builder.assert_one(sha224_targets.digest[i].target);
+            } else {
+                builder.assert_zero(sha224_targets.digest[i].target);
+            }
+        }
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+    #[test]
+    fn test_sha256_compress_native() {
+        // The single padded block of "abc".
+        let mut block = [0u32; 16];
+        block[0] = 0x61626380;
+        block[15] = 24;
+        let digest = sha2::Sha256::digest(b"abc");
+        let expected: [u32; 8] = core::array::from_fn(|i| {
+            u32::from_be_bytes(digest[4 * i..4 * i + 4].try_into().unwrap())
+        });
+        assert_eq!(sha256_compress_native(H256, block), expected);
     }
 
     #[test]
-    fn test_variable_length_sha256_circuit() -> anyhow::Result<()> {
+    fn test_sha256_compress() -> anyhow::Result<()> {
         const D: usize = 2;
         type C = PoseidonGoldilocksConfig;
         type F = <C as GenericConfig<D>>::F;
-        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::wide_ecc_config());
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
 
-        let msg = EXAMPLE_MESSAGE;
+        let words: Vec<u32> = EXAMPLE_MESSAGE
+            .chunks(4)
+            .take(24)
+            .map(|w| u32::from_be_bytes(w.try_into().unwrap()))
+            .collect();
+        let state: [u32; 8] = words[..8].try_into()?;
+        let block: [u32; 16] = words[8..].try_into()?;
+        let expected = sha256_compress_native(state, block);
+
+        let state_targets: [_; 8] = core::array::from_fn(|_| builder.add_virtual_u32_target());
+        let block_targets: [_; 16] = core::array::from_fn(|_| builder.add_virtual_u32_target());
+        let out = sha256_compress(&mut builder, state_targets, block_targets);
+        for (word, &expected) in out.iter().zip(&expected) {
+            let expected = builder.constant_u32(expected);
+            builder.connect_u32(*word, expected);
+        }
 
-        let digest = sha2::Sha256::digest(&msg);
+        let mut pw = PartialWitness::new();
+        for (&target, &value) in state_targets.iter().zip(&state) {
+            pw.set_u32_target(target, value)?;
+        }
+        for (&target, &value) in block_targets.iter().zip(&block) {
+            pw.set_u32_target(target, value)?;
+        }
 
-        let tot_bits = 512 * 48; // 48 blocks, instead of 47 blocks which is needed
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+    #[test]
+    fn test_sha256_circuit_with_midstate() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
 
-        // Create SHA256 circuit using your make_circuits function
-        let sha256_targets = make_variable_length_circuits(&mut builder, tot_bits);
+        let msg = &EXAMPLE_MESSAGE[..300];
+        let digest = sha2::Sha256::digest(msg);
+        let (prefix, suffix) = msg.split_at(128);
+        let midstate = Sha256Midstate::from_prefix(prefix);
+
+        let sha256_targets =
+            make_circuits_with_midstate(&mut builder, &midstate, (suffix.len() * 8) as u64);
 
-        // Create witness
         let mut pw = PartialWitness::new();
+        for (target, bit) in sha256_targets.message.iter().zip(array_to_bits(suffix)) {
+            pw.set_bool_target(*target, bit)?;
+        }
 
-        fill_variable_length_circuits::<F, D>(&mut pw, &msg, tot_bits, &sha256_targets)?;
+        let expected_digest_bits = array_to_bits(&digest);
+        for (i, expected_digest_bit) in expected_digest_bits.iter().enumerate() {
+            if *expected_digest_bit {
+                builder.assert_one(sha256_targets.digest[i].target);
+            } else {
+                builder.assert_zero(sha256_targets.digest[i].target);
+            }
+        }
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_sha256_circuit_with_public_midstate() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let msg = &EXAMPLE_MESSAGE[..300];
+        let digest = sha2::Sha256::digest(msg);
+        let (prefix, suffix) = msg.split_at(192);
+        let midstate = Sha256Midstate::from_prefix(prefix);
+
+        let sha256_targets =
+            make_circuits_with_public_midstate(&mut builder, (suffix.len() * 8) as u64);
+
+        let mut pw = PartialWitness::new();
+        fill_midstate_targets(&mut pw, &sha256_targets, &midstate)?;
+        for (target, bit) in sha256_targets.message.iter().zip(array_to_bits(suffix)) {
+            pw.set_bool_target(*target, bit)?;
+        }
 
-        // Convert expected digest bytes to bits and set as witness
         let expected_digest_bits = array_to_bits(&digest);
         for (i, expected_digest_bit) in expected_digest_bits.iter().enumerate() {
             if *expected_digest_bit {
@@ -801,20 +2232,258 @@ pub mod tests {
             }
         }
 
-        println!(
-            "Constructing inner proof with {} gates",
-            builder.num_gates()
-        );
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        let expected_public_inputs: Vec<F> = midstate
+            .state
+            .iter()
+            .chain([&midstate.blocks])
+            .map(|&x| F::from_canonical_u32(x))
+            .collect();
+        assert_eq!(proof.public_inputs, expected_public_inputs);
+        data.verify(proof)
+    }
 
-        // Build the circuit
+    #[test]
+    fn test_public_midstate_rejects_non_canonical_length() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let (prefix, suffix) = EXAMPLE_MESSAGE[..300].split_at(192);
+        let midstate = Sha256Midstate::from_prefix(prefix);
+        let targets = make_circuits_with_public_midstate(&mut builder, (suffix.len() * 8) as u64);
         let data = builder.build::<C>();
 
-        // Generate proof
-        let proof = data.prove(pw)?;
+        // lo + 2^32 * hi is congruent to the total mod p, but hashes a
+        // different length.
+        let total = midstate.blocks * 512 + suffix.len() as u32 * 8;
+        let mut pw = PartialWitness::new();
+        for (&target, &value) in targets.state.iter().zip(&midstate.state) {
+            pw.set_u32_target(target, value)?;
+        }
+        pw.set_u32_target(targets.blocks, midstate.blocks)?;
+        pw.set_u32_target(targets.total_len[0], u32::MAX)?;
+        pw.set_u32_target(targets.total_len[1], total + 1)?;
+        for (target, bit) in targets.message.iter().zip(array_to_bits(suffix)) {
+            pw.set_bool_target(*target, bit)?;
+        }
+        assert!(data.prove(pw).is_err());
+        Ok(())
+    }
 
-        // Verify proof
-        data.verify(proof)?;
+    #[test]
+    fn test_sha256_circuit_public_inputs() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        // Word aligned and not, so the last message word is full and partial.
+        for msg in [&EXAMPLE_MESSAGE[..60], &EXAMPLE_MESSAGE[..61]] {
+            let msg_len_in_bits = msg.len() * 8;
+            let digest = sha2::Sha256::digest(msg);
+            for public_message in [
+                PublicMessage::None,
+                PublicMessage::Words,
+                PublicMessage::Commitment,
+            ] {
+                let mut builder =
+                    CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+                let targets =
+                    make_public_circuits(&mut builder, msg_len_in_bits as u64, public_message);
+                let data = builder.build::<C>();
+
+                let mut pw = PartialWitness::new();
+                for (target, bit) in targets.message.iter().zip(array_to_bits(msg)) {
+                    pw.set_bool_target(*target, bit)?;
+                }
+                let proof = data.prove(pw)?;
+
+                assert_eq!(digest_from_proof(&proof), digest.as_slice());
+                assert_eq!(
+                    proof.public_inputs,
+                    public_inputs_native::<F>(msg, public_message)
+                );
+                data.verify(proof)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sha256_fused_rounds() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        // Three blocks, so both round loops run on carried-over state.
+        let msg = &EXAMPLE_MESSAGE[..150];
+        let mut digests = Vec::new();
+        let mut num_gates = Vec::new();
+        for backend in [Sha256Backend::Bits, Sha256Backend::FusedRounds] {
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let targets =
+                make_circuits_u32_with_backend(&mut builder, (msg.len() * 8) as u64, backend);
+            for word in targets.digest {
+                builder.register_public_input(word.0);
+            }
+            num_gates.push(builder.num_gates());
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            for (target, bit) in targets.message.iter().zip(array_to_bits(msg)) {
+                pw.set_bool_target(*target, bit)?;
+            }
+            let proof = data.prove(pw)?;
+            digests.push(digest_from_proof(&proof));
+            data.verify(proof)?;
+        }
+
+        assert_eq!(digests[0], digests[1]);
+        assert_eq!(digests[0], sha2::Sha256::digest(msg).as_slice());
+        assert!(num_gates[1] < num_gates[0]);
+        Ok(())
+    }
+
+    /// `BitRoundFunctions` with every σ as 32 `Xor3Gate` ops on shifted
+    /// bits, as before `SigmaGate`.
+    struct Xor3Sigmas;
+
+    impl Xor3Sigmas {
+        fn xor3_shifts<F: RichField + Extendable<D>, const D: usize>(
+            builder: &mut CircuitBuilder<F, D>,
+            x: &LazyU32WithBits,
+            shifts: [Shift; 3],
+        ) -> LazyU32WithBits {
+            let mut bits = x.get_bits(builder);
+            bits.reverse();
+            let zero = builder._false();
+            let shifted = shifts.map(|shift| shift.apply(&bits, zero));
+            let mut out: Vec<_> = (0..32)
+                .map(|i| builder.add_xor3(shifted[0][i], shifted[1][i], shifted[2][i]))
+                .collect();
+            out.reverse();
+            LazyU32WithBits::from_bits(out)
+        }
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> Sha256RoundFunctions<F, D> for Xor3Sigmas {
+        type Word = LazyU32WithBits;
+
+        fn u32_to_word(&mut self, builder: &mut CircuitBuilder<F, D>, x: U32Target) -> Self::Word {
+            BitRoundFunctions.u32_to_word(builder, x)
+        }
+
+        fn word_to_u32(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> U32Target {
+            BitRoundFunctions.word_to_u32(builder, x)
+        }
+
+        fn big_sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+            Self::xor3_shifts(
+                builder,
+                x,
+                [Shift::Rotr(2), Shift::Rotr(13), Shift::Rotr(22)],
+            )
+        }
+
+        fn big_sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+            Self::xor3_shifts(
+                builder,
+                x,
+                [Shift::Rotr(6), Shift::Rotr(11), Shift::Rotr(25)],
+            )
+        }
+
+        fn sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+            Self::xor3_shifts(builder, x, [Shift::Rotr(7), Shift::Rotr(18), Shift::Shr(3)])
+        }
+
+        fn sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+            Self::xor3_shifts(
+                builder,
+                x,
+                [Shift::Rotr(17), Shift::Rotr(19), Shift::Shr(10)],
+            )
+        }
+
+        fn ch(
+            &mut self,
+            builder: &mut CircuitBuilder<F, D>,
+            e: &Self::Word,
+            f: &Self::Word,
+            g: &Self::Word,
+        ) -> Self::Word {
+            BitRoundFunctions.ch(builder, e, f, g)
+        }
 
+        fn maj(
+            &mut self,
+            builder: &mut CircuitBuilder<F, D>,
+            a: &Self::Word,
+            b: &Self::Word,
+            c: &Self::Word,
+        ) -> Self::Word {
+            BitRoundFunctions.maj(builder, a, b, c)
+        }
+
+        fn add(
+            &mut self,
+            builder: &mut CircuitBuilder<F, D>,
+            a: &Self::Word,
+            b: &Self::Word,
+        ) -> Self::Word {
+            BitRoundFunctions.add(builder, a, b)
+        }
+
+        fn add_many(
+            &mut self,
+            builder: &mut CircuitBuilder<F, D>,
+            xs: &[Self::Word],
+        ) -> Self::Word {
+            BitRoundFunctions.add_many(builder, xs)
+        }
+    }
+
+    #[test]
+    fn test_sigma_gate_saves_rows() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let msg = &EXAMPLE_MESSAGE[..150];
+        let msg_len_in_bits = (msg.len() * 8) as u64;
+        for config in [
+            CircuitConfig::standard_recursion_config(),
+            CircuitConfig::wide_ecc_config(),
+        ] {
+            let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+            make_circuits(&mut builder, msg_len_in_bits);
+            let sigma_gates = builder.num_gates();
+
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let (message, words) = fixed_length_message(&mut builder, msg_len_in_bits);
+            let digest = sha256_padded_words_with(&mut builder, &mut Xor3Sigmas, &H256, &words);
+            digest_to_bits(&mut builder, &digest);
+            assert!(sigma_gates < builder.num_gates());
+
+            // The baseline computes the same digest.
+            for word in digest {
+                builder.register_public_input(word.0);
+            }
+            let data = builder.build::<C>();
+            let mut pw = PartialWitness::new();
+            for (target, bit) in message.iter().zip(array_to_bits(msg)) {
+                pw.set_bool_target(*target, bit)?;
+            }
+            let proof = data.prove(pw)?;
+            assert_eq!(
+                digest_from_proof(&proof),
+                sha2::Sha256::digest(msg).as_slice()
+            );
+        }
         Ok(())
     }
 }
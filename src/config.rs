@@ -0,0 +1,193 @@
+use plonky2::{
+    field::extension::Extendable, hash::hash_types::RichField, plonk::circuit_data::CircuitConfig,
+};
+
+use crate::estimate::estimate_circuits_u32;
+
+/// Named `CircuitConfig`s for SHA-256 circuits.
+///
+/// How many `Xor3Gate`, `SigmaGate` or `BaseSumGateOptimized` ops fit in a row
+/// depends on `num_routed_wires`, so the best choice depends on the message
+/// length as well as on what the proof is for. `Sha256ConfigPreset::circuit_size`
+/// computes the size of a preset for a given length without laying out the
+/// circuit, and `fastest_for` picks one.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Sha256ConfigPreset {
+    /// `standard_recursion_config`: 135 wires, 80 routed. Proofs of this shape
+    /// can be verified by the recursion circuits in `crate::recursion` and by
+    /// plonky2's standard recursive verifier.
+    #[default]
+    RecursionFriendly,
+    /// `wide_ecc_config`: 234 wires, 130 routed. More bit ops fit in a row, so
+    /// long messages need fewer rows.
+    FastestProving,
+    /// `standard_recursion_config` with a rate of 1/64 and 14 FRI queries
+    /// instead of 1/8 and 28, for the same conjectured security. Proofs are
+    /// about half the size, for an 8 times larger low-degree extension.
+    SmallestProof,
+}
+
+/// Size of a SHA-256 circuit under some config, before the few gates `build`
+/// adds (public inputs, constants, lookup tables).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Sha256CircuitSize {
+    pub num_gates: usize,
+    /// `log2` of the padded number of rows.
+    pub degree_bits: usize,
+    pub num_wires: usize,
+    pub rate_bits: usize,
+}
+
+impl Sha256CircuitSize {
+    /// Number of cells in the low-degree extension of the wire polynomials,
+    /// which is what proving time mostly scales with.
+    pub fn lde_cells(&self) -> usize {
+        self.num_wires << (self.degree_bits + self.rate_bits)
+    }
+}
+
+impl Sha256ConfigPreset {
+    pub const ALL: [Sha256ConfigPreset; 3] = [
+        Sha256ConfigPreset::RecursionFriendly,
+        Sha256ConfigPreset::FastestProving,
+        Sha256ConfigPreset::SmallestProof,
+    ];
+
+    pub fn config(self) -> CircuitConfig {
+        match self {
+            Sha256ConfigPreset::RecursionFriendly => CircuitConfig::standard_recursion_config(),
+            Sha256ConfigPreset::FastestProving => CircuitConfig::wide_ecc_config(),
+            Sha256ConfigPreset::SmallestProof => {
+                let mut config = CircuitConfig::standard_recursion_config();
+                config.fri_config.rate_bits = 6;
+                config.fri_config.num_query_rounds = 14;
+                config
+            }
+        }
+    }
+
+    /// Size of the circuit `make_circuits_u32` builds for `msg_len_in_bits`
+    /// under this preset. It comes from `estimate_circuits_u32`, which matches
+    /// the layout row for row without laying out any gates, so it is cheap
+    /// enough to call before every proof.
+    pub fn circuit_size<F: RichField + Extendable<D>, const D: usize>(
+        self,
+        msg_len_in_bits: u64,
+    ) -> Sha256CircuitSize {
+        let config = self.config();
+        let num_gates = estimate_circuits_u32::<F, D>(&config, msg_len_in_bits).num_gates();
+        Sha256CircuitSize {
+            num_gates,
+            degree_bits: num_gates.next_power_of_two().trailing_zeros() as usize,
+            num_wires: config.num_wires,
+            rate_bits: config.fri_config.rate_bits,
+        }
+    }
+
+    /// The preset with the smallest low-degree extension for a message of
+    /// `msg_len_in_bits`, i.e. the one expected to prove fastest. Which one
+    /// that is changes with the length, as row counts are padded to powers of
+    /// two.
+    pub fn fastest_for<F: RichField + Extendable<D>, const D: usize>(msg_len_in_bits: u64) -> Self {
+        Self::ALL
+            .into_iter()
+            .min_by_key(|preset| preset.circuit_size::<F, D>(msg_len_in_bits).lde_cells())
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+
+    use crate::circuit::{
+        array_to_bits, digest_from_proof, make_circuits_u32, make_public_circuits, PublicMessage,
+    };
+    use crate::config::Sha256ConfigPreset;
+    use sha2::Digest;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_presets_prove() -> anyhow::Result<()> {
+        let msg = b"abc";
+        let mut proof_sizes = Vec::new();
+        for preset in Sha256ConfigPreset::ALL {
+            let mut builder = CircuitBuilder::<F, D>::new(preset.config());
+            let targets =
+                make_public_circuits(&mut builder, (msg.len() * 8) as u64, PublicMessage::None);
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            for (target, bit) in targets.message.iter().zip(array_to_bits(msg)) {
+                pw.set_bool_target(*target, bit)?;
+            }
+            let proof = data.prove(pw)?;
+            assert_eq!(
+                digest_from_proof(&proof),
+                sha2::Sha256::digest(msg).as_slice()
+            );
+            proof_sizes.push(proof.to_bytes().len());
+            data.verify(proof)?;
+        }
+        // Half the FRI queries give a smaller proof than either other preset.
+        let [recursion, fastest, smallest] = proof_sizes[..] else {
+            unreachable!()
+        };
+        assert!(smallest < recursion, "{proof_sizes:?}");
+        assert!(smallest < fastest, "{proof_sizes:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_fastest_for() {
+        for msg_len in [3, 64, 1000, 2828] {
+            let msg_len_in_bits = msg_len * 8;
+            let [recursion, fastest_proving, smallest] =
+                Sha256ConfigPreset::ALL.map(|preset| preset.circuit_size::<F, D>(msg_len_in_bits));
+
+            // The sizes are the real layout, not an approximation.
+            for (preset, size) in
+                Sha256ConfigPreset::ALL
+                    .iter()
+                    .zip([recursion, fastest_proving, smallest])
+            {
+                let mut builder = CircuitBuilder::<F, D>::new(preset.config());
+                make_circuits_u32(&mut builder, msg_len_in_bits);
+                assert_eq!(
+                    size.num_gates,
+                    builder.num_gates(),
+                    "{msg_len} bytes, {preset:?}"
+                );
+            }
+            // More routed wires fit more ops per row; the rate only changes
+            // the extension.
+            assert!(
+                fastest_proving.num_gates < recursion.num_gates,
+                "{msg_len} bytes"
+            );
+            assert_eq!(smallest.num_gates, recursion.num_gates);
+            assert_eq!(smallest.lde_cells(), 8 * recursion.lde_cells());
+
+            let fastest = Sha256ConfigPreset::fastest_for::<F, D>(msg_len_in_bits);
+            let min_cells = [recursion, fastest_proving, smallest]
+                .map(|size| size.lde_cells())
+                .into_iter()
+                .min();
+            assert_eq!(
+                Some(fastest.circuit_size::<F, D>(msg_len_in_bits).lde_cells()),
+                min_cells
+            );
+            // The higher rate never pays off for proving time.
+            assert_ne!(fastest, Sha256ConfigPreset::SmallestProof);
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::marker::PhantomData;
+
+use plonky2::{
+    field::extension::Extendable,
+    gates::{
+        arithmetic_base::ArithmeticGate, base_sum::BaseSumGate, constant::ConstantGate, gate::Gate,
+        public_input::PublicInputGate,
+    },
+    hash::hash_types::RichField,
+    plonk::circuit_data::CircuitConfig,
+};
+use plonky2_u32::gates::{add_many_u32::U32AddManyGate, arithmetic_u32::U32ArithmeticGate};
+
+use crate::circuit::{H256, K256};
+use crate::gates::{BaseSumGateOptimized, ChGate, MajGate, SigmaGate, SigmaKind};
+
+/// Predicted size of a SHA-256 circuit, computed from the config alone.
+///
+/// Gate ids are the ones `CircuitBuilder::print_gate_counts` reports, so an
+/// estimate can be compared against a real layout line by line.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CircuitEstimate {
+    /// Rows per gate id laid out by the circuit function.
+    pub gate_counts: BTreeMap<String, usize>,
+    /// Rows per gate id that `build` adds on top: the `PublicInputGate`, and
+    /// a `ConstantGate` slot for every distinct constant.
+    pub build_gate_counts: BTreeMap<String, usize>,
+}
+
+impl CircuitEstimate {
+    /// Rows before `build`, i.e. what `builder.num_gates()` returns after the
+    /// circuit function.
+    pub fn num_gates(&self) -> usize {
+        self.gate_counts.values().sum()
+    }
+
+    /// Rows after `build`, before padding.
+    pub fn num_rows(&self) -> usize {
+        self.num_gates() + self.build_gate_counts.values().sum::<usize>()
+    }
+
+    /// `log2` of the padded number of rows, i.e. `common.degree_bits()` of the
+    /// built circuit.
+    pub fn degree_bits(&self) -> usize {
+        self.num_rows().next_power_of_two().trailing_zeros() as usize
+    }
+}
+
+/// Mirrors the bookkeeping of `CircuitBuilder::find_slot`: ops of a gate are
+/// packed into rows per (gate id, constants), so only op counts are tracked.
+struct Estimator<'a, F: RichField + Extendable<D>, const D: usize> {
+    config: &'a CircuitConfig,
+    /// `(ops per row, ops)` per slot key.
+    slots: BTreeMap<(String, Vec<u64>), (usize, usize)>,
+    /// Gates added a whole row at a time.
+    rows: BTreeMap<String, usize>,
+    constants: BTreeSet<u64>,
+    _phantom: PhantomData<F>,
+}
+
+impl<'a, F: RichField + Extendable<D>, const D: usize> Estimator<'a, F, D> {
+    fn new(config: &'a CircuitConfig) -> Self {
+        Self {
+            config,
+            slots: BTreeMap::new(),
+            rows: BTreeMap::new(),
+            constants: BTreeSet::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn ops<G: Gate<F, D>>(&mut self, gate: G, constants: &[F], ops: usize) {
+        if ops == 0 {
+            return;
+        }
+        let key = (
+            gate.id(),
+            constants.iter().map(|c| c.to_canonical_u64()).collect(),
+        );
+        let slot = self.slots.entry(key).or_insert((gate.num_ops(), 0));
+        slot.1 += ops;
+    }
+
+    fn rows<G: Gate<F, D>>(&mut self, gate: G, rows: usize) {
+        *self.rows.entry(gate.id()).or_default() += rows;
+    }
+
+    fn constant(&mut self, c: F) {
+        self.constants.insert(c.to_canonical_u64());
+    }
+
+    fn constant_usize(&mut self, c: usize) {
+        self.constant(F::from_canonical_usize(c));
+    }
+
+    /// `ops` calls to `builder.arithmetic(const_0, const_1, ..)` that are not
+    /// folded away.
+    fn arithmetic(&mut self, const_0: F, const_1: F, ops: usize) {
+        let gate = ArithmeticGate::new_from_config(self.config);
+        self.ops(gate, &[const_0, const_1], ops);
+    }
+
+    fn add(&mut self, ops: usize) {
+        self.arithmetic(F::ONE, F::ONE, ops);
+    }
+
+    /// `sub`, `mul_sub` and `not`.
+    fn sub(&mut self, ops: usize) {
+        self.arithmetic(F::ONE, F::NEG_ONE, ops);
+    }
+
+    /// `mul` and `and`.
+    fn mul(&mut self, ops: usize) {
+        self.arithmetic(F::ONE, F::ZERO, ops);
+    }
+
+    /// `is_equal(x, y)`: `not`, `sub` and two `mul`s, less the `sub` when `y`
+    /// is the zero constant.
+    fn is_equal(&mut self, y_is_zero: bool) {
+        self.constant(F::ZERO);
+        self.constant(F::ONE);
+        self.sub(if y_is_zero { 1 } else { 2 });
+        self.mul(2);
+    }
+
+    /// `le_sum_optimized` of 32 bits.
+    fn le_sums(&mut self, ops: usize) {
+        let gate = BaseSumGateOptimized::<2>::new_from_config(self.config, 34);
+        self.constant(F::ZERO);
+        self.ops(gate, &[], ops);
+    }
+
+    /// `u32_to_bits_target`.
+    fn splits(&mut self, ops: usize) {
+        let gate = BaseSumGateOptimized::<2>::new_from_config(self.config, 32);
+        self.ops(gate, &[], ops);
+    }
+
+    fn add_u32(&mut self, ops: usize) {
+        let gate = U32ArithmeticGate::<F, D>::new_from_config(self.config);
+        self.constant(F::ONE);
+        self.ops(gate, &[], ops);
+    }
+
+    fn add_many_u32(&mut self, num_addends: usize, ops: usize) {
+        let gate = U32AddManyGate::<F, D>::new_from_config(self.config, num_addends);
+        self.constant(F::ZERO);
+        self.ops(gate, &[], ops);
+    }
+
+    /// `compress_lazy` on a state held as `U32Target`s.
+    fn compress(&mut self) {
+        for k in K256 {
+            self.constant(F::from_canonical_u32(k));
+        }
+        for (kind, ops) in [
+            (SigmaKind::BigSigma0, 64),
+            (SigmaKind::BigSigma1, 64),
+            (SigmaKind::Sigma0, 48),
+            (SigmaKind::Sigma1, 48),
+        ] {
+            self.ops(
+                SigmaGate::<F, D>::new_from_config(self.config, kind),
+                &[],
+                ops,
+            );
+        }
+        self.ops(ChGate::<F, D>::new_from_config(self.config), &[], 64 * 32);
+        self.ops(MajGate::<F, D>::new_from_config(self.config), &[], 64 * 32);
+        // Σ0, Σ1, Ch and Maj of every round and σ0, σ1 of every schedule step
+        // are recomposed for the additions.
+        self.le_sums(4 * 64 + 2 * 48);
+        // Splits happen when `BitRoundFunctions` first needs the bits of a
+        // word, in `compress_with`:
+        // - 6: `a`, `b`, `c` (for `big_sigma0` and `maj`) and `e`, `f`, `g`
+        //   (for `big_sigma1` and `ch`) of the input state; `d` and `h` are
+        //   only added.
+        // - 2 * 63: `new_a` and `new_e` of every round but the last; the last
+        //   round's are only added to `state`.
+        // - 61: the schedule words `sigma0` reads (`x[(i + 1) & 0x0f]`, i.e.
+        //   W1..=W48) and `sigma1` reads (`x[(i + 14) & 0x0f]`, W14..=W61).
+        self.splits(6 + 2 * 63 + 61);
+        // In rounds 0..16, `t1` is one `add_many` of five words.
+        self.add_many_u32(5, 16);
+        // - 16 * 3: `t2`, `new_e` and `new_a` in rounds 0..16.
+        // - 48 * (3 + 7): in rounds 16..64, `s0_add_s1`, `s0_add_s1_add_x`
+        //   and the new `x[i & 0xf]` for the schedule, then `h_add_sigma1`,
+        //   `h_add_sigma1_add_ch_e_f_g`, `h_add_sigma1_add_ch_e_f_g_add_k256`,
+        //   `t1`, `t2`, `new_e` and `new_a`.
+        // - 8: adding the working variables back into `state`.
+        self.add_u32(16 * 3 + 48 * (3 + 7) + 8);
+    }
+
+    fn finish(self) -> CircuitEstimate {
+        let mut gate_counts = self.rows;
+        for ((id, _), (ops_per_row, ops)) in self.slots {
+            *gate_counts.entry(id).or_default() += ops.div_ceil(ops_per_row);
+        }
+
+        let mut build_gate_counts = BTreeMap::new();
+        build_gate_counts.insert(Gate::<F, D>::id(&PublicInputGate), 1);
+        if !self.constants.is_empty() {
+            let gate = ConstantGate::new(self.config.num_constants);
+            let rows = self.constants.len().div_ceil(gate.num_consts);
+            build_gate_counts.insert(Gate::<F, D>::id(&gate), rows);
+        }
+        CircuitEstimate {
+            gate_counts,
+            build_gate_counts,
+        }
+    }
+}
+
+/// Estimates the circuit `make_circuits_u32(builder, msg_len_in_bits)` lays
+/// out under `config`, without laying it out. Public inputs registered on top
+/// are not accounted for.
+pub fn estimate_circuits_u32<F: RichField + Extendable<D>, const D: usize>(
+    config: &CircuitConfig,
+    msg_len_in_bits: u64,
+) -> CircuitEstimate {
+    fixed_length_estimator::<F, D>(config, msg_len_in_bits).finish()
+}
+
+/// Estimates the circuit `make_circuits(builder, msg_len_in_bits)` lays out
+/// under `config`, without laying it out. Public inputs registered on top are
+/// not accounted for.
+pub fn estimate_circuits<F: RichField + Extendable<D>, const D: usize>(
+    config: &CircuitConfig,
+    msg_len_in_bits: u64,
+) -> CircuitEstimate {
+    let mut est = fixed_length_estimator::<F, D>(config, msg_len_in_bits);
+    est.splits(8);
+    est.finish()
+}
+
+fn fixed_length_estimator<F: RichField + Extendable<D>, const D: usize>(
+    config: &CircuitConfig,
+    msg_len_in_bits: u64,
+) -> Estimator<'_, F, D> {
+    let mut est = Estimator::<F, D>::new(config);
+    let block_count = (msg_len_in_bits + 65).div_ceil(512) as usize;
+
+    // Padding bits are constants; every padded word is recomposed.
+    est.constant(F::ZERO);
+    est.constant(F::ONE);
+    est.le_sums(16 * block_count);
+
+    for c in H256 {
+        est.constant(F::from_canonical_u32(c));
+    }
+    for _ in 0..block_count {
+        est.compress();
+    }
+    est
+}
+
+/// Estimates the circuit `make_variable_length_circuits(builder,
+/// max_total_bits)` lays out under `config`, without laying it out. Public
+/// inputs registered on top are not accounted for.
+pub fn estimate_variable_length_circuits<F: RichField + Extendable<D>, const D: usize>(
+    config: &CircuitConfig,
+    max_total_bits: usize,
+) -> CircuitEstimate {
+    assert_eq!(
+        max_total_bits % 512,
+        0,
+        "max_total_bits must be a multiple of 512"
+    );
+    let tot_blocks = max_total_bits / 512;
+    let mut est = Estimator::<F, D>::new(config);
+    est.le_sums(max_total_bits / 32);
+
+    // One-hot `last_block`, summed with `add_many` (whose first addition, to
+    // zero, folds away).
+    for blk in 0..tot_blocks {
+        est.constant_usize(blk + 1);
+        est.is_equal(false);
+    }
+    est.add(tot_blocks.saturating_sub(1));
+
+    // `constrain_msg_blocks`.
+    est.constant_usize(576);
+    est.add(1);
+    est.arithmetic(-F::from_canonical_u64(512), F::ONE, 1);
+    let gate = BaseSumGate::<2>::new_from_config::<F>(config);
+    est.rows(gate, 9usize.div_ceil(gate.num_limbs));
+
+    // `len_bits`.
+    est.splits(1);
+
+    // `constrain_padding_block`. Positions are constants, so `add_const`
+    // folds; `is_equal` against position zero saves its `sub`, and the first
+    // `is_pad` update adds to zero.
+    for pos in 0..max_total_bits {
+        let offset = pos % 512;
+        est.constant_usize(offset);
+        est.constant_usize(pos);
+        est.is_equal(pos == 0);
+        if pos > 0 {
+            est.add(1);
+        }
+        est.sub(1);
+        if offset >= 512 - 32 {
+            est.arithmetic(F::NEG_ONE, F::ONE, 1);
+        }
+    }
+
+    for c in H256 {
+        est.constant(F::from_canonical_u32(c));
+    }
+    // `do_block` starts as the one constant, so the first `and` folds.
+    est.constant(F::ONE);
+    for blk in 0..tot_blocks {
+        est.constant_usize(blk);
+        est.is_equal(false);
+        est.sub(1);
+        if blk > 0 {
+            est.mul(1);
+        }
+        est.compress();
+        // Two `mul_sub`s per `select`.
+        est.sub(2 * 8);
+    }
+    est.splits(8);
+    est.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use plonky2::{
+        field::types::Field,
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::{CircuitConfig, CircuitData},
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+
+    use crate::circuit::{make_circuits, make_circuits_u32, make_variable_length_circuits};
+    use crate::estimate::{
+        estimate_circuits, estimate_circuits_u32, estimate_variable_length_circuits,
+        CircuitEstimate,
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Rows per gate id of a built circuit, read off the selector
+    /// polynomials: a row runs gate `i` when selector `selector_indices[i]`
+    /// is `i` there.
+    fn built_gate_counts(data: &CircuitData<F, C, D>) -> BTreeMap<String, usize> {
+        let selectors_info = &data.common.selectors_info;
+        let selectors: Vec<Vec<F>> = data.prover_only.constants_sigmas_commitment.polynomials
+            [..selectors_info.num_selectors()]
+            .iter()
+            .map(|poly| poly.clone().fft().values)
+            .collect();
+        let mut counts = BTreeMap::new();
+        for (i, gate) in data.common.gates.iter().enumerate() {
+            let selector = &selectors[selectors_info.selector_indices[i]];
+            let rows = selector
+                .iter()
+                .filter(|&&s| s == F::from_canonical_usize(i))
+                .count();
+            *counts.entry(gate.0.id()).or_default() += rows;
+        }
+        counts.remove("NoopGate");
+        counts
+    }
+
+    fn assert_matches_build(estimate: &CircuitEstimate, builder: CircuitBuilder<F, D>, what: &str) {
+        assert_eq!(estimate.num_gates(), builder.num_gates(), "{what}");
+        let data = builder.build::<C>();
+        assert_eq!(estimate.degree_bits(), data.common.degree_bits(), "{what}");
+
+        let mut estimated_counts = estimate.gate_counts.clone();
+        for (id, rows) in &estimate.build_gate_counts {
+            *estimated_counts.entry(id.clone()).or_default() += rows;
+        }
+        assert_eq!(built_gate_counts(&data), estimated_counts, "{what}");
+    }
+
+    #[test]
+    fn test_estimate_matches_layout() {
+        for config in [
+            CircuitConfig::standard_recursion_config(),
+            CircuitConfig::wide_ecc_config(),
+        ] {
+            for msg_len in [0, 3, 55, 56, 200] {
+                let msg_len_in_bits = msg_len * 8;
+                let estimate = estimate_circuits::<F, D>(&config, msg_len_in_bits);
+                let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+                make_circuits(&mut builder, msg_len_in_bits);
+                assert_matches_build(&estimate, builder, &format!("{msg_len} bytes"));
+
+                let estimate = estimate_circuits_u32::<F, D>(&config, msg_len_in_bits);
+                let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+                make_circuits_u32(&mut builder, msg_len_in_bits);
+                assert_matches_build(&estimate, builder, &format!("{msg_len} bytes, u32 digest"));
+            }
+            for max_total_bits in [512, 1024, 2048] {
+                let estimate = estimate_variable_length_circuits::<F, D>(&config, max_total_bits);
+                let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+                make_variable_length_circuits(&mut builder, max_total_bits);
+                assert_matches_build(&estimate, builder, &format!("up to {max_total_bits} bits"));
+            }
+        }
+    }
+}
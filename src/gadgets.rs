@@ -9,7 +9,11 @@ use plonky2::{
 // Assuming U32Target is defined somewhere like this:
 // pub struct U32Target(pub Target);
 
-use crate::gates::{BaseSplitGeneratorOptimized, BaseSumGateOptimized, ChGate, MajGate};
+use crate::gates::{
+    BaseSplitGeneratorOptimized, BaseSumGateOptimized, ChGate, MajGate, Sha256RoundGate, SigmaGate,
+    SigmaKind,
+};
+use plonky2_u32::gadgets::arithmetic_u32::U32Target;
 // Re-export the gate for convenience
 use crate::gates::Xor3Gate;
 use core::borrow::Borrow;
@@ -19,6 +23,11 @@ pub trait XorOps<F: RichField + Extendable<D>, const D: usize> {
     fn add_xor3(&mut self, a: BoolTarget, b: BoolTarget, c: BoolTarget) -> BoolTarget;
     fn add_maj(&mut self, a: BoolTarget, b: BoolTarget, c: BoolTarget) -> BoolTarget;
     fn add_ch(&mut self, a: BoolTarget, b: BoolTarget, c: BoolTarget) -> BoolTarget;
+    /// Applies the sigma function `kind` to 32 big-endian bits in one `SigmaGate` op.
+    fn add_sigma(&mut self, kind: SigmaKind, bits: &[BoolTarget]) -> Vec<BoolTarget>;
+    /// Computes a round's new `a` and `e` from `d, h, Σ1(e), Ch, k, w, Σ0(a), Maj`
+    /// in one `Sha256RoundGate` op.
+    fn add_sha256_round(&mut self, inputs: [U32Target; 8]) -> (U32Target, U32Target);
     fn le_sum_optimized(&mut self, bits: impl Iterator<Item = impl Borrow<BoolTarget>>) -> Target;
     fn split_le_base_optimized<const B: usize>(
         &mut self,
@@ -67,6 +76,34 @@ impl<F: RichField + Extendable<D>, const D: usize> XorOps<F, D> for CircuitBuild
         self.connect(c.target, wire_c);
         BoolTarget::new_unsafe(Target::wire(gate, 3 + op_ind * 4))
     }
+    fn add_sigma(&mut self, kind: SigmaKind, bits: &[BoolTarget]) -> Vec<BoolTarget> {
+        assert_eq!(bits.len(), 32);
+        let gate = SigmaGate::new_from_config(&self.config, kind);
+        let constants = vec![];
+        let (gate, i) = self.find_slot(gate, &constants, &constants);
+        for (bit, &a) in bits.iter().enumerate() {
+            let wire = Target::wire(gate, SigmaGate::<F, D>::wire_input(i, bit));
+            self.connect(a.target, wire);
+        }
+        (0..32)
+            .map(|bit| {
+                let wire = SigmaGate::<F, D>::wire_output(i, bit);
+                BoolTarget::new_unsafe(Target::wire(gate, wire))
+            })
+            .collect()
+    }
+    fn add_sha256_round(&mut self, inputs: [U32Target; 8]) -> (U32Target, U32Target) {
+        let gate = Sha256RoundGate::new_from_config(&self.config);
+        let constants = vec![];
+        let (gate, i) = self.find_slot(gate, &constants, &constants);
+        for (j, input) in inputs.iter().enumerate() {
+            let wire = Target::wire(gate, Sha256RoundGate::<F, D>::wire_input(i, j));
+            self.connect(input.0, wire);
+        }
+        let new_a = Target::wire(gate, Sha256RoundGate::<F, D>::wire_new_a(i));
+        let new_e = Target::wire(gate, Sha256RoundGate::<F, D>::wire_new_e(i));
+        (U32Target(new_a), U32Target(new_e))
+    }
 
     /// Takes an iterator of bits `(b_i)` and returns `sum b_i * 2^i`, i.e.,
     /// the number with little-endian bit representation given by `bits`.
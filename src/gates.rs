@@ -9,7 +9,7 @@ use plonky2::{
     iop::{
         ext_target::ExtensionTarget,
         generator::{GeneratedValues, SimpleGenerator, WitnessGeneratorRef},
-        target::{BoolTarget, Target},
+        target::Target,
         witness::{PartitionWitness, Witness, WitnessWrite},
     },
     plonk::{
@@ -18,7 +18,7 @@ use plonky2::{
         plonk_common::{reduce_with_powers, reduce_with_powers_ext_circuit},
         vars::{EvaluationTargets, EvaluationVars},
     },
-    util::serialization::{Buffer, IoResult, Read, Write},
+    util::serialization::{Buffer, IoError, IoResult, Read, Write},
 };
 
 #[derive(Copy, Clone, Debug)]
@@ -146,8 +146,8 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for Xor3Gate<F, D>
 }
 
 // Witness generator for the gate
-#[derive(Debug, Clone)]
-struct Xor3Generator<F: RichField + Extendable<D>, const D: usize> {
+#[derive(Debug, Clone, Default)]
+pub struct Xor3Generator<F: RichField + Extendable<D>, const D: usize> {
     row: usize,
     i: usize,
     _phantom: PhantomData<F>,
@@ -155,7 +155,7 @@ struct Xor3Generator<F: RichField + Extendable<D>, const D: usize> {
 
 impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Xor3Generator<F, D> {
     fn id(&self) -> String {
-        format!("Xor3Generator(row={})", self.row)
+        "Xor3Generator".to_string()
     }
 
     fn dependencies(&self) -> Vec<Target> {
@@ -325,8 +325,8 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for MajGate<F, D>
 }
 
 // Witness generator for the gate
-#[derive(Debug, Clone)]
-struct MajGenerator<F: RichField + Extendable<D>, const D: usize> {
+#[derive(Debug, Clone, Default)]
+pub struct MajGenerator<F: RichField + Extendable<D>, const D: usize> {
     row: usize,
     i: usize,
     _phantom: PhantomData<F>,
@@ -334,7 +334,7 @@ struct MajGenerator<F: RichField + Extendable<D>, const D: usize> {
 
 impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for MajGenerator<F, D> {
     fn id(&self) -> String {
-        format!("MajGenerator(row={})", self.row)
+        "MajGenerator".to_string()
     }
 
     fn dependencies(&self) -> Vec<Target> {
@@ -498,8 +498,8 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ChGate<F, D> {
 }
 
 // Witness generator for the gate
-#[derive(Debug, Clone)]
-struct ChGenerator<F: RichField + Extendable<D>, const D: usize> {
+#[derive(Debug, Clone, Default)]
+pub struct ChGenerator<F: RichField + Extendable<D>, const D: usize> {
     row: usize,
     i: usize,
     _phantom: PhantomData<F>,
@@ -507,7 +507,7 @@ struct ChGenerator<F: RichField + Extendable<D>, const D: usize> {
 
 impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for ChGenerator<F, D> {
     fn id(&self) -> String {
-        format!("ChGenerator(row={})", self.row)
+        "ChGenerator".to_string()
     }
 
     fn dependencies(&self) -> Vec<Target> {
@@ -556,6 +556,549 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for ChG
     }
 }
 
+/// Which of SHA-256's four sigma functions a `SigmaGate` computes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SigmaKind {
+    /// `ROTR 2 ^ ROTR 13 ^ ROTR 22`
+    #[default]
+    BigSigma0,
+    /// `ROTR 6 ^ ROTR 11 ^ ROTR 25`
+    BigSigma1,
+    /// `ROTR 7 ^ ROTR 18 ^ SHR 3`
+    Sigma0,
+    /// `ROTR 17 ^ ROTR 19 ^ SHR 10`
+    Sigma1,
+}
+
+impl SigmaKind {
+    const ALL: [SigmaKind; 4] = [
+        SigmaKind::BigSigma0,
+        SigmaKind::BigSigma1,
+        SigmaKind::Sigma0,
+        SigmaKind::Sigma1,
+    ];
+
+    /// The two rotations, then the third rotation or (for the small sigmas)
+    /// the shift.
+    const fn amounts(self) -> [usize; 3] {
+        match self {
+            SigmaKind::BigSigma0 => [2, 13, 22],
+            SigmaKind::BigSigma1 => [6, 11, 25],
+            SigmaKind::Sigma0 => [7, 18, 3],
+            SigmaKind::Sigma1 => [17, 19, 10],
+        }
+    }
+
+    /// Input bits XORed into output bit `i`, both indexed big-endian. The
+    /// shifted-out term is missing for the top bits of the small sigmas.
+    pub fn input_bits(self, i: usize) -> Vec<usize> {
+        let [r1, r2, r3] = self.amounts();
+        let mut bits = vec![(i + 32 - r1) % 32, (i + 32 - r2) % 32];
+        match self {
+            SigmaKind::BigSigma0 | SigmaKind::BigSigma1 => bits.push((i + 32 - r3) % 32),
+            SigmaKind::Sigma0 | SigmaKind::Sigma1 if i >= r3 => bits.push(i - r3),
+            SigmaKind::Sigma0 | SigmaKind::Sigma1 => {}
+        }
+        bits
+    }
+
+    fn to_usize(self) -> usize {
+        Self::ALL.iter().position(|&k| k == self).unwrap()
+    }
+
+    fn from_usize(i: usize) -> IoResult<Self> {
+        Self::ALL.get(i).copied().ok_or(IoError)
+    }
+}
+
+/// A whole sigma function in one op: 32 big-endian input bits in wires
+/// `0..32` and the 32 output bits in `32..64`, with the rotations baked into
+/// the constraints. Inputs must be boolean.
+///
+/// Compared with 32 `Xor3Gate` ops, the input is copied in once rather than
+/// three times, and the op fits in a single row of the standard config.
+#[derive(Copy, Clone, Debug)]
+pub struct SigmaGate<F: RichField + Extendable<D>, const D: usize> {
+    pub kind: SigmaKind,
+    pub num_ops: usize,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SigmaGate<F, D> {
+    pub(crate) const WIRES_PER_OP: usize = 64;
+
+    /// Determine the maximum number of operations that can fit in one gate for the given config.
+    pub(crate) const fn num_ops(config: &CircuitConfig) -> usize {
+        config.num_routed_wires / Self::WIRES_PER_OP
+    }
+
+    pub fn new_from_config(config: &CircuitConfig, kind: SigmaKind) -> Self {
+        let num_ops = Self::num_ops(config);
+        assert!(num_ops > 0, "SigmaGate needs at least 64 routed wires");
+        Self {
+            kind,
+            num_ops,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub(crate) const fn wire_input(i: usize, bit: usize) -> usize {
+        i * Self::WIRES_PER_OP + bit
+    }
+
+    pub(crate) const fn wire_output(i: usize, bit: usize) -> usize {
+        i * Self::WIRES_PER_OP + 32 + bit
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for SigmaGate<F, D> {
+    fn id(&self) -> String {
+        format!("Sigma({:?})", self.kind)
+    }
+
+    fn num_wires(&self) -> usize {
+        self.num_ops * Self::WIRES_PER_OP
+    }
+    fn num_constants(&self) -> usize {
+        0
+    }
+    fn degree(&self) -> usize {
+        4
+    }
+    fn num_constraints(&self) -> usize {
+        self.num_ops * 32
+    }
+
+    fn eval_unfiltered(&self, vars: EvaluationVars<F, D>) -> Vec<F::Extension> {
+        let mut res = Vec::new();
+        for i in 0..self.num_ops {
+            for bit in 0..32 {
+                let inputs: Vec<_> = self
+                    .kind
+                    .input_bits(bit)
+                    .into_iter()
+                    .map(|j| vars.local_wires[Self::wire_input(i, j)])
+                    .collect();
+                let o = vars.local_wires[Self::wire_output(i, bit)];
+
+                // a ^ b = (a - b)^2 and a ^ b ^ c = ((a - b)^2 - c)^2 on bits.
+                let u = inputs[0] - inputs[1];
+                let mut expected = u * u;
+                if let Some(&c) = inputs.get(2) {
+                    let v = expected - c;
+                    expected = v * v;
+                }
+                res.push(o - expected);
+            }
+        }
+        res
+    }
+
+    fn eval_unfiltered_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: EvaluationTargets<D>,
+    ) -> Vec<ExtensionTarget<D>> {
+        let mut res = Vec::new();
+        for i in 0..self.num_ops {
+            for bit in 0..32 {
+                let inputs: Vec<_> = self
+                    .kind
+                    .input_bits(bit)
+                    .into_iter()
+                    .map(|j| vars.local_wires[Self::wire_input(i, j)])
+                    .collect();
+                let o = vars.local_wires[Self::wire_output(i, bit)];
+
+                let u = builder.sub_extension(inputs[0], inputs[1]);
+                let mut expected = builder.mul_extension(u, u);
+                if let Some(&c) = inputs.get(2) {
+                    let v = builder.sub_extension(expected, c);
+                    expected = builder.mul_extension(v, v);
+                }
+                res.push(builder.sub_extension(o, expected));
+            }
+        }
+        res
+    }
+
+    fn generators(&self, row: usize, _local_constants: &[F]) -> Vec<WitnessGeneratorRef<F, D>> {
+        (0..self.num_ops)
+            .map(|i| {
+                WitnessGeneratorRef::new(
+                    SigmaGenerator::<F, D> {
+                        row,
+                        i,
+                        kind: self.kind,
+                        _phantom: PhantomData,
+                    }
+                    .adapter(),
+                )
+            })
+            .collect()
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.kind.to_usize())?;
+        dst.write_usize(self.num_ops)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let kind = SigmaKind::from_usize(src.read_usize()?)?;
+        let num_ops = src.read_usize()?;
+        Ok(Self {
+            kind,
+            num_ops,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SigmaGenerator<F: RichField + Extendable<D>, const D: usize> {
+    row: usize,
+    i: usize,
+    kind: SigmaKind,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for SigmaGenerator<F, D> {
+    fn id(&self) -> String {
+        "SigmaGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        (0..32)
+            .map(|bit| Target::wire(self.row, SigmaGate::<F, D>::wire_input(self.i, bit)))
+            .collect()
+    }
+
+    fn run_once(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+    ) -> Result<()> {
+        let input: Vec<u64> = (0..32)
+            .map(|bit| {
+                let wire = SigmaGate::<F, D>::wire_input(self.i, bit);
+                witness
+                    .get_target(Target::wire(self.row, wire))
+                    .to_canonical_u64()
+            })
+            .collect();
+        for bit in 0..32 {
+            let o = self
+                .kind
+                .input_bits(bit)
+                .into_iter()
+                .fold(0, |acc, j| acc ^ input[j])
+                & 1;
+            out_buffer.set_target(
+                Target::wire(self.row, SigmaGate::<F, D>::wire_output(self.i, bit)),
+                F::from_canonical_u64(o),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.row)?;
+        dst.write_usize(self.i)?;
+        dst.write_usize(self.kind.to_usize())
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let row = src.read_usize()?;
+        let i = src.read_usize()?;
+        let kind = SigmaKind::from_usize(src.read_usize()?)?;
+        Ok(Self {
+            row,
+            i,
+            kind,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// One SHA-256 round's additions in a single op. With `t1 = h + Σ1(e) + Ch +
+/// k + w` and `t2 = Σ0(a) + Maj`, it constrains
+///
+/// `t1 + d = e' + 2^32 * carry_e` and `t1 + t2 = a' + 2^32 * carry_a`,
+///
+/// where `e'` and `a'` are range-checked through 16 base-4 limbs each and the
+/// carries through two. The eight input words must be range-checked to 32 bits.
+///
+/// Inputs and outputs take the first `10 * num_ops` (routed) wires; the limbs
+/// follow, `NUM_LIMBS` per op.
+#[derive(Copy, Clone, Debug)]
+pub struct Sha256RoundGate<F: RichField + Extendable<D>, const D: usize> {
+    pub num_ops: usize,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Sha256RoundGate<F, D> {
+    pub(crate) const NUM_INPUTS: usize = 8;
+    pub(crate) const ROUTED_WIRES_PER_OP: usize = Self::NUM_INPUTS + 2;
+    /// Base-4 limbs of `e'`, `carry_e`, `a'` and `carry_a`.
+    const NUM_LIMBS: usize = 2 * (16 + 2);
+
+    /// Determine the maximum number of operations that can fit in one gate for the given config.
+    pub(crate) const fn num_ops(config: &CircuitConfig) -> usize {
+        let by_routed = config.num_routed_wires / Self::ROUTED_WIRES_PER_OP;
+        let by_total = config.num_wires / (Self::ROUTED_WIRES_PER_OP + Self::NUM_LIMBS);
+        if by_routed < by_total {
+            by_routed
+        } else {
+            by_total
+        }
+    }
+
+    pub fn new_from_config(config: &CircuitConfig) -> Self {
+        Self {
+            num_ops: Self::num_ops(config),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Input `j`, in the order `d, h, Σ1(e), Ch, k, w, Σ0(a), Maj`.
+    pub(crate) const fn wire_input(i: usize, j: usize) -> usize {
+        i * Self::ROUTED_WIRES_PER_OP + j
+    }
+
+    pub(crate) const fn wire_new_e(i: usize) -> usize {
+        i * Self::ROUTED_WIRES_PER_OP + Self::NUM_INPUTS
+    }
+
+    pub(crate) const fn wire_new_a(i: usize) -> usize {
+        i * Self::ROUTED_WIRES_PER_OP + Self::NUM_INPUTS + 1
+    }
+
+    /// Limbs of `e'` (0..16), `carry_e` (16..18), `a'` (18..34) and `carry_a`
+    /// (34..36), least significant first.
+    fn wire_limb(&self, i: usize, limb: usize) -> usize {
+        self.num_ops * Self::ROUTED_WIRES_PER_OP + i * Self::NUM_LIMBS + limb
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Default for Sha256RoundGate<F, D> {
+    fn default() -> Self {
+        Self::new_from_config(&CircuitConfig::standard_recursion_config())
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for Sha256RoundGate<F, D> {
+    fn id(&self) -> String {
+        "Sha256Round()".to_string()
+    }
+
+    fn num_wires(&self) -> usize {
+        self.num_ops * (Self::ROUTED_WIRES_PER_OP + Self::NUM_LIMBS)
+    }
+    fn num_constants(&self) -> usize {
+        0
+    }
+    fn degree(&self) -> usize {
+        4
+    }
+    fn num_constraints(&self) -> usize {
+        self.num_ops * (4 + Self::NUM_LIMBS)
+    }
+
+    fn eval_unfiltered(&self, vars: EvaluationVars<F, D>) -> Vec<F::Extension> {
+        let base = F::Extension::from_canonical_u64(4);
+        let two_32 = F::Extension::from_canonical_u64(1 << 32);
+        let mut res = Vec::new();
+        for i in 0..self.num_ops {
+            let input = |j| vars.local_wires[Self::wire_input(i, j)];
+            let t1 = input(1) + input(2) + input(3) + input(4) + input(5);
+            let t2 = input(6) + input(7);
+            let new_e = vars.local_wires[Self::wire_new_e(i)];
+            let new_a = vars.local_wires[Self::wire_new_a(i)];
+
+            let limbs: Vec<_> = (0..Self::NUM_LIMBS)
+                .map(|l| vars.local_wires[self.wire_limb(i, l)])
+                .collect();
+            let e_limbs = reduce_with_powers(&limbs[0..16], base);
+            let carry_e = reduce_with_powers(&limbs[16..18], base);
+            let a_limbs = reduce_with_powers(&limbs[18..34], base);
+            let carry_a = reduce_with_powers(&limbs[34..36], base);
+
+            res.push(t1 + input(0) - new_e - carry_e * two_32);
+            res.push(t1 + t2 - new_a - carry_a * two_32);
+            res.push(e_limbs - new_e);
+            res.push(a_limbs - new_a);
+            for limb in limbs {
+                res.push(
+                    (0..4)
+                        .map(|x| limb - F::Extension::from_canonical_u64(x))
+                        .product(),
+                );
+            }
+        }
+        res
+    }
+
+    fn eval_unfiltered_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: EvaluationTargets<D>,
+    ) -> Vec<ExtensionTarget<D>> {
+        let base = builder.constant(F::from_canonical_u64(4));
+        let two_32 = builder.constant_extension(F::Extension::from_canonical_u64(1 << 32));
+        let mut res = Vec::new();
+        for i in 0..self.num_ops {
+            let input: Vec<_> = (0..Self::NUM_INPUTS)
+                .map(|j| vars.local_wires[Self::wire_input(i, j)])
+                .collect();
+            let t1 = builder.add_many_extension(&input[1..6]);
+            let t2 = builder.add_extension(input[6], input[7]);
+            let new_e = vars.local_wires[Self::wire_new_e(i)];
+            let new_a = vars.local_wires[Self::wire_new_a(i)];
+
+            let limbs: Vec<_> = (0..Self::NUM_LIMBS)
+                .map(|l| vars.local_wires[self.wire_limb(i, l)])
+                .collect();
+            let e_limbs = reduce_with_powers_ext_circuit(builder, &limbs[0..16], base);
+            let carry_e = reduce_with_powers_ext_circuit(builder, &limbs[16..18], base);
+            let a_limbs = reduce_with_powers_ext_circuit(builder, &limbs[18..34], base);
+            let carry_a = reduce_with_powers_ext_circuit(builder, &limbs[34..36], base);
+
+            let t1_add_d = builder.add_extension(t1, input[0]);
+            let carry_e_shifted = builder.mul_extension(carry_e, two_32);
+            let e_sum = builder.add_extension(new_e, carry_e_shifted);
+            res.push(builder.sub_extension(t1_add_d, e_sum));
+
+            let t1_add_t2 = builder.add_extension(t1, t2);
+            let carry_a_shifted = builder.mul_extension(carry_a, two_32);
+            let a_sum = builder.add_extension(new_a, carry_a_shifted);
+            res.push(builder.sub_extension(t1_add_t2, a_sum));
+
+            res.push(builder.sub_extension(e_limbs, new_e));
+            res.push(builder.sub_extension(a_limbs, new_a));
+
+            for limb in limbs {
+                let mut acc = builder.one_extension();
+                for x in 0..4 {
+                    // acc' = acc (limb - x)
+                    let neg_x = -F::from_canonical_u64(x);
+                    acc = builder.arithmetic_extension(F::ONE, neg_x, acc, limb, acc);
+                }
+                res.push(acc);
+            }
+        }
+        res
+    }
+
+    fn generators(&self, row: usize, _local_constants: &[F]) -> Vec<WitnessGeneratorRef<F, D>> {
+        (0..self.num_ops)
+            .map(|i| {
+                WitnessGeneratorRef::new(
+                    Sha256RoundGenerator::<F, D> {
+                        row,
+                        i,
+                        num_ops: self.num_ops,
+                        _phantom: PhantomData,
+                    }
+                    .adapter(),
+                )
+            })
+            .collect()
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.num_ops)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        Ok(Self {
+            num_ops: src.read_usize()?,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Sha256RoundGenerator<F: RichField + Extendable<D>, const D: usize> {
+    row: usize,
+    i: usize,
+    num_ops: usize,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
+    for Sha256RoundGenerator<F, D>
+{
+    fn id(&self) -> String {
+        "Sha256RoundGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        (0..Sha256RoundGate::<F, D>::NUM_INPUTS)
+            .map(|j| Target::wire(self.row, Sha256RoundGate::<F, D>::wire_input(self.i, j)))
+            .collect()
+    }
+
+    fn run_once(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+    ) -> Result<()> {
+        let gate = Sha256RoundGate::<F, D> {
+            num_ops: self.num_ops,
+            _phantom: PhantomData,
+        };
+        let input: Vec<u64> = (0..Sha256RoundGate::<F, D>::NUM_INPUTS)
+            .map(|j| {
+                let wire = Sha256RoundGate::<F, D>::wire_input(self.i, j);
+                witness
+                    .get_target(Target::wire(self.row, wire))
+                    .to_canonical_u64()
+            })
+            .collect();
+        let t1: u64 = input[1..6].iter().sum();
+        let t2 = input[6] + input[7];
+        let e_sum = t1 + input[0];
+        let a_sum = t1 + t2;
+
+        let mut set = |wire: usize, value: u64| {
+            out_buffer.set_target(Target::wire(self.row, wire), F::from_canonical_u64(value))
+        };
+        set(
+            Sha256RoundGate::<F, D>::wire_new_e(self.i),
+            e_sum & 0xffffffff,
+        )?;
+        set(
+            Sha256RoundGate::<F, D>::wire_new_a(self.i),
+            a_sum & 0xffffffff,
+        )?;
+        for (start, sum) in [(0, e_sum), (18, a_sum)] {
+            // 16 limbs for the low word, then 2 for the carry.
+            for l in 0..18 {
+                set(gate.wire_limb(self.i, start + l), (sum >> (2 * l)) & 3)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.row)?;
+        dst.write_usize(self.i)?;
+        dst.write_usize(self.num_ops)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let row = src.read_usize()?;
+        let i = src.read_usize()?;
+        let num_ops = src.read_usize()?;
+        Ok(Self {
+            row,
+            i,
+            num_ops,
+            _phantom: PhantomData,
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct BaseSumGateOptimized<const B: usize> {
     pub num_limbs: usize,
@@ -702,7 +1245,7 @@ impl<F: RichField + Extendable<D>, const B: usize, const D: usize> SimpleGenerat
     for BaseSumGeneratorOptimized<B>
 {
     fn id(&self) -> String {
-        format!("BaseSumGenerator + Base: {B}")
+        format!("BaseSumGeneratorOptimized + Base: {B}")
     }
 
     fn dependencies(&self) -> Vec<Target> {
@@ -723,21 +1266,18 @@ impl<F: RichField + Extendable<D>, const B: usize, const D: usize> SimpleGenerat
         out_buffer: &mut GeneratedValues<F>,
     ) -> Result<()> {
         let offset = self.i * (self.num_limbs + 1);
-        let limbs: Vec<BoolTarget> = (0..self.num_limbs)
+        // Limbs are read as field elements rather than bools: for B > 2 they are
+        // not bits, and an out-of-range limb must be rejected by the gate
+        // constraints, not by a panic here.
+        let sum = (0..self.num_limbs)
             .map(|j| {
-                BoolTarget::new_unsafe(Target::wire(
+                witness.get_target(Target::wire(
                     self.row,
                     BaseSumGateOptimized::<B>::START_LIMBS + j + offset,
                 ))
             })
-            .collect();
-        let sum = limbs
-            .iter()
-            .map(|&t| witness.get_bool_target(t))
             .rev()
-            .fold(F::ZERO, |acc, limb| {
-                acc * F::from_canonical_usize(B) + F::from_bool(limb)
-            });
+            .fold(F::ZERO, |acc, limb| acc * F::from_canonical_usize(B) + limb);
 
         out_buffer.set_target(
             Target::wire(self.row, BaseSumGateOptimized::<B>::WIRE_SUM + offset),
@@ -770,7 +1310,7 @@ impl<F: RichField + Extendable<D>, const B: usize, const D: usize> SimpleGenerat
     for BaseSplitGeneratorOptimized<B>
 {
     fn id(&self) -> String {
-        format!("BaseSplitGenerator + Base: {B}")
+        format!("BaseSplitGeneratorOptimized + Base: {B}")
     }
 
     fn dependencies(&self) -> Vec<Target> {
@@ -837,9 +1377,10 @@ impl<F: RichField + Extendable<D>, const B: usize, const D: usize> SimpleGenerat
 mod tests {
     use anyhow::Result;
 
-    use crate::gates::BaseSumGateOptimized;
+    use crate::gates::{BaseSumGateOptimized, Sha256RoundGate, SigmaGate, SigmaKind};
     use plonky2::field::goldilocks_field::GoldilocksField;
     use plonky2::gates::gate_testing::{test_eval_fns, test_low_degree};
+    use plonky2::plonk::circuit_data::CircuitConfig;
     use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
 
     #[test]
@@ -854,4 +1395,41 @@ mod tests {
         type F = <C as GenericConfig<D>>::F;
         test_eval_fns::<F, C, _, D>(BaseSumGateOptimized::<6>::new(11, 5))
     }
+
+    #[test]
+    fn sigma_low_degree() {
+        let config = CircuitConfig::standard_recursion_config();
+        for kind in SigmaKind::ALL {
+            test_low_degree::<GoldilocksField, _, 4>(SigmaGate::new_from_config(&config, kind))
+        }
+    }
+
+    #[test]
+    fn sigma_eval_fns() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+        for kind in SigmaKind::ALL {
+            test_eval_fns::<F, C, _, D>(SigmaGate::new_from_config(&config, kind))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn round_low_degree() {
+        test_low_degree::<GoldilocksField, _, 4>(Sha256RoundGate::new_from_config(
+            &CircuitConfig::standard_recursion_config(),
+        ))
+    }
+
+    #[test]
+    fn round_eval_fns() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        test_eval_fns::<F, C, _, D>(Sha256RoundGate::new_from_config(
+            &CircuitConfig::standard_recursion_config(),
+        ))
+    }
 }
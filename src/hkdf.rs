@@ -0,0 +1,225 @@
+use anyhow::Result;
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::{target::BoolTarget, witness::PartialWitness},
+    plonk::circuit_builder::CircuitBuilder,
+};
+
+use crate::circuit::{add_virtual_bytes, array_to_bits, digest_to_bits, fill_bytes};
+use crate::hmac::{
+    hmac_sha256, hmac_sha256_key_states, hmac_sha256_native, hmac_sha256_with_key_states,
+};
+
+/// Longest output `hkdf_expand` can produce: 255 blocks of 32 bytes.
+pub const HKDF_SHA256_MAX_OKM_BYTES: usize = 255 * 32;
+
+/// HKDF-Extract (RFC 5869): the 256-bit pseudorandom key
+/// `HMAC(salt, ikm)`, as big-endian bits. An empty salt stands for 32 zero
+/// bytes, which gives the same HMAC key block.
+pub fn hkdf_extract<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    salt: &[BoolTarget],
+    ikm: &[BoolTarget],
+) -> Vec<BoolTarget> {
+    let prk = hmac_sha256(builder, salt, ikm);
+    digest_to_bits(builder, &prk)
+}
+
+/// HKDF-Expand (RFC 5869): `okm_len_in_bytes` bytes of output keying material
+/// from `prk` and `info`, as big-endian bits. The PRK key states are derived
+/// once and shared by all `T(i) = HMAC(prk, T(i-1) || info || i)`.
+pub fn hkdf_expand<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    prk: &[BoolTarget],
+    info: &[BoolTarget],
+    okm_len_in_bytes: usize,
+) -> Vec<BoolTarget> {
+    assert!(
+        okm_len_in_bytes <= HKDF_SHA256_MAX_OKM_BYTES,
+        "HKDF-SHA256 output is at most {HKDF_SHA256_MAX_OKM_BYTES} bytes (got {okm_len_in_bytes})"
+    );
+    assert_eq!(info.len() % 8, 0, "info must be whole bytes");
+    let key = hmac_sha256_key_states(builder, prk);
+
+    let mut okm = Vec::with_capacity(okm_len_in_bytes * 8);
+    let mut t = Vec::new();
+    for i in 1..=okm_len_in_bytes.div_ceil(32) {
+        let mut msg = t;
+        msg.extend_from_slice(info);
+        for bit in array_to_bits(&[i as u8]) {
+            msg.push(builder.constant_bool(bit));
+        }
+        let block = hmac_sha256_with_key_states(builder, &key, &msg);
+        t = digest_to_bits(builder, &block);
+        okm.extend_from_slice(&t);
+    }
+    okm.truncate(okm_len_in_bytes * 8);
+    okm
+}
+
+/// Native HKDF-SHA256 (RFC 5869), extract then expand.
+pub fn hkdf_sha256_native(
+    salt: &[u8],
+    ikm: &[u8],
+    info: &[u8],
+    okm_len_in_bytes: usize,
+) -> Vec<u8> {
+    assert!(okm_len_in_bytes <= HKDF_SHA256_MAX_OKM_BYTES);
+    let prk = hmac_sha256_native(salt, ikm);
+    let mut okm = Vec::with_capacity(okm_len_in_bytes);
+    let mut t = Vec::new();
+    for i in 1..=okm_len_in_bytes.div_ceil(32) {
+        t.extend_from_slice(info);
+        t.push(i as u8);
+        t = hmac_sha256_native(&prk, &t).to_vec();
+        okm.extend_from_slice(&t);
+    }
+    okm.truncate(okm_len_in_bytes);
+    okm
+}
+
+pub struct HkdfSha256Targets {
+    pub salt: Vec<BoolTarget>,
+    /// Input keying material bits. They are private witnesses.
+    pub ikm: Vec<BoolTarget>,
+    pub info: Vec<BoolTarget>,
+    /// The pseudorandom key from `hkdf_extract`.
+    pub prk: Vec<BoolTarget>,
+    /// The output keying material from `hkdf_expand`.
+    pub okm: Vec<BoolTarget>,
+}
+
+/// Builds an HKDF-SHA256 circuit for fixed salt, IKM and info lengths,
+/// deriving `okm_len_in_bytes` bytes.
+pub fn make_hkdf_sha256_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    salt_len_in_bytes: usize,
+    ikm_len_in_bytes: usize,
+    info_len_in_bytes: usize,
+    okm_len_in_bytes: usize,
+) -> HkdfSha256Targets {
+    let salt = add_virtual_bytes(builder, salt_len_in_bytes);
+    let ikm = add_virtual_bytes(builder, ikm_len_in_bytes);
+    let info = add_virtual_bytes(builder, info_len_in_bytes);
+    let prk = hkdf_extract(builder, &salt, &ikm);
+    let okm = hkdf_expand(builder, &prk, &info, okm_len_in_bytes);
+
+    HkdfSha256Targets {
+        salt,
+        ikm,
+        info,
+        prk,
+        okm,
+    }
+}
+
+pub fn fill_hkdf_sha256_circuits<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    salt: &[u8],
+    ikm: &[u8],
+    info: &[u8],
+    targets: &HkdfSha256Targets,
+) -> Result<()> {
+    fill_bytes(pw, salt, &targets.salt)?;
+    fill_bytes(pw, ikm, &targets.ikm)?;
+    fill_bytes(pw, info, &targets.info)
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        field::types::Field,
+        iop::witness::PartialWitness,
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+
+    use crate::circuit::array_to_bits;
+    use crate::hkdf::{fill_hkdf_sha256_circuits, hkdf_sha256_native, make_hkdf_sha256_circuits};
+    use crate::test_util::hex;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// RFC 5869 test cases 1 to 3 as `(ikm, salt, info, prk, okm)`.
+    fn rfc5869_vectors() -> Vec<[Vec<u8>; 5]> {
+        [
+            [
+                "0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b",
+                "000102030405060708090a0b0c",
+                "f0f1f2f3f4f5f6f7f8f9",
+                "077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5",
+                "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865",
+            ],
+            [
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\
+                 202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f\
+                 404142434445464748494a4b4c4d4e4f",
+                "606162636465666768696a6b6c6d6e6f707172737475767778797a7b7c7d7e7f\
+                 808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f\
+                 a0a1a2a3a4a5a6a7a8a9aaabacadaeaf",
+                "b0b1b2b3b4b5b6b7b8b9babbbcbdbebfc0c1c2c3c4c5c6c7c8c9cacbcccdcecf\
+                 d0d1d2d3d4d5d6d7d8d9dadbdcdddedfe0e1e2e3e4e5e6e7e8e9eaebecedeeef\
+                 f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff",
+                "06a6b88c5853361a06104c9ceb35b45cef760014904671014a193f40c15fc244",
+                "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c\
+                 59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71\
+                 cc30c58179ec3e87c14c01d5c1f3434f1d87",
+            ],
+            [
+                "0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b",
+                "",
+                "",
+                "19ef24a32c717b167f33a91d6f648bdf96596776afdb6377ac434c1c293ccb04",
+                "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8",
+            ],
+        ]
+        .into_iter()
+        .map(|case| case.map(hex))
+        .collect()
+    }
+
+    #[test]
+    fn test_hkdf_sha256_native() {
+        for [ikm, salt, info, _, okm] in rfc5869_vectors() {
+            assert_eq!(hkdf_sha256_native(&salt, &ikm, &info, okm.len()), okm);
+        }
+    }
+
+    #[test]
+    fn test_hkdf_sha256_circuit() -> anyhow::Result<()> {
+        // Case 2 has a salt longer than a block and three output blocks; case 3
+        // has an empty salt and info.
+        for [ikm, salt, info, prk, okm] in rfc5869_vectors() {
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let targets = make_hkdf_sha256_circuits(
+                &mut builder,
+                salt.len(),
+                ikm.len(),
+                info.len(),
+                okm.len(),
+            );
+            for bit in targets.prk.iter().chain(&targets.okm) {
+                builder.register_public_input(bit.target);
+            }
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            fill_hkdf_sha256_circuits(&mut pw, &salt, &ikm, &info, &targets)?;
+            let proof = data.prove(pw)?;
+            let expected: Vec<_> = array_to_bits(&[prk, okm].concat())
+                .into_iter()
+                .map(F::from_bool)
+                .collect();
+            assert_eq!(proof.public_inputs, expected);
+            data.verify(proof)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,395 @@
+use anyhow::Result;
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::{target::BoolTarget, witness::PartialWitness},
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_u32::gadgets::arithmetic_u32::{CircuitBuilderU32, U32Target};
+use sha2::{Digest, Sha256};
+
+use crate::circuit::{
+    add_virtual_bytes, digest_to_bits, fill_bytes, fill_variable_length_message, hash_suffix,
+    make_variable_length_suffix_circuits, message_words, sha256_compress, Sha256Midstate, H256,
+};
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+/// SHA-256 block size in bytes; longer keys are hashed first.
+pub const HMAC_SHA256_BLOCK_BYTES: usize = 64;
+
+pub struct HmacSha256Targets {
+    /// Key bits. They are private witnesses; register them as public inputs
+    /// only if the key is not secret.
+    pub key: Vec<BoolTarget>,
+    /// Message bits, without padding.
+    pub message: Vec<BoolTarget>,
+    /// The MAC as eight big-endian words.
+    pub mac: [U32Target; 8],
+}
+
+/// Like `HmacSha256Targets`, for a message of variable length.
+pub struct VariableLengthHmacSha256Targets {
+    pub key: Vec<BoolTarget>,
+    /// Padded message bits of the inner hash, after the key block. Fill them
+    /// with `fill_variable_length_hmac_sha256_circuits`.
+    pub message: Vec<BoolTarget>,
+    pub mac: [U32Target; 8],
+    pub msg_len: U32Target,
+    pub msg_blocks: U32Target,
+}
+
+/// The key zero-padded to a block, after hashing it if it is longer.
+fn key_block_native(key: &[u8]) -> [u8; HMAC_SHA256_BLOCK_BYTES] {
+    let mut key_block = [0u8; HMAC_SHA256_BLOCK_BYTES];
+    if key.len() > HMAC_SHA256_BLOCK_BYTES {
+        key_block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        key_block[..key.len()].copy_from_slice(key);
+    }
+    key_block
+}
+
+/// Native HMAC-SHA256 (RFC 2104).
+pub fn hmac_sha256_native(key: &[u8], msg: &[u8]) -> [u8; 32] {
+    let key_block = key_block_native(key);
+    let inner = Sha256::new()
+        .chain_update(key_block.map(|b| b ^ IPAD))
+        .chain_update(msg)
+        .finalize();
+    Sha256::new()
+        .chain_update(key_block.map(|b| b ^ OPAD))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// Native counterpart of `hmac_sha256_key_states`, as `(inner, outer)`.
+pub fn hmac_sha256_key_states_native(key: &[u8]) -> ([u32; 8], [u32; 8]) {
+    let key_block = key_block_native(key);
+    (
+        Sha256Midstate::from_prefix(&key_block.map(|b| b ^ IPAD)).state,
+        Sha256Midstate::from_prefix(&key_block.map(|b| b ^ OPAD)).state,
+    )
+}
+
+/// SHA-256 states after the inner and outer padded key blocks. MACs under
+/// one key can share them, saving two compressions each.
+#[derive(Copy, Clone, Debug)]
+pub struct HmacSha256KeyStates {
+    pub inner: [U32Target; 8],
+    pub outer: [U32Target; 8],
+}
+
+/// Derives the key states of `key`, given as big-endian bits of whole bytes.
+/// Keys longer than a block are hashed first. The key bits are constrained to
+/// be boolean here.
+pub fn hmac_sha256_key_states<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    key: &[BoolTarget],
+) -> HmacSha256KeyStates {
+    assert_eq!(key.len() % 8, 0, "key must be whole bytes");
+    let mut key_block = if key.len() > HMAC_SHA256_BLOCK_BYTES * 8 {
+        let iv = H256.map(|c| builder.constant_u32(c));
+        let total_len = [
+            builder.constant_u32((key.len() as u64 >> 32) as u32),
+            builder.constant_u32(key.len() as u32),
+        ];
+        let (_, digest) = hash_suffix(builder, iv, total_len, key);
+        digest_to_bits(builder, &digest)
+    } else {
+        key.to_vec()
+    };
+    let zero = builder.constant_bool(false);
+    key_block.resize(HMAC_SHA256_BLOCK_BYTES * 8, zero);
+
+    HmacSha256KeyStates {
+        inner: padded_key_state(builder, &key_block, IPAD),
+        outer: padded_key_state(builder, &key_block, OPAD),
+    }
+}
+
+/// The SHA-256 state after the block `key_block ^ pad`.
+fn padded_key_state<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    key_block: &[BoolTarget],
+    pad: u8,
+) -> [U32Target; 8] {
+    let bits: Vec<_> = key_block
+        .iter()
+        .enumerate()
+        .map(|(i, &bit)| {
+            if (pad >> (7 - i % 8)) & 1 == 1 {
+                builder.not(bit)
+            } else {
+                bit
+            }
+        })
+        .collect();
+    let words = message_words(builder, &bits);
+    let iv = H256.map(|c| builder.constant_u32(c));
+    sha256_compress(builder, iv, words.try_into().unwrap())
+}
+
+/// Compresses the last block of a hash whose input is one block followed by
+/// `digest`: the digest, the `1` bit, zero fill and the length.
+fn compress_digest_block<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    state: [U32Target; 8],
+    digest: [U32Target; 8],
+) -> [U32Target; 8] {
+    let total_len_in_bits = (HMAC_SHA256_BLOCK_BYTES + 32) as u32 * 8;
+    let zero = builder.zero_u32();
+    let mut block = digest.to_vec();
+    block.push(builder.constant_u32(0x80000000));
+    block.resize(15, zero);
+    block.push(builder.constant_u32(total_len_in_bits));
+    sha256_compress(builder, state, block.try_into().unwrap())
+}
+
+/// The outer hash `SHA-256((key_block ^ opad) || inner)`.
+fn outer_hash<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    key: &HmacSha256KeyStates,
+    inner: [U32Target; 8],
+) -> [U32Target; 8] {
+    compress_digest_block(builder, key.outer, inner)
+}
+
+/// HMAC-SHA256 of a 32-byte message given as eight big-endian words, such as
+/// an earlier MAC. This takes two compressions and no bit decomposition.
+pub fn hmac_sha256_of_digest<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    key: &HmacSha256KeyStates,
+    digest: [U32Target; 8],
+) -> [U32Target; 8] {
+    let inner = compress_digest_block(builder, key.inner, digest);
+    outer_hash(builder, key, inner)
+}
+
+/// HMAC-SHA256 of `msg` under the key behind `key`, with the message given as
+/// big-endian bits. The message bits are constrained to be boolean here.
+pub fn hmac_sha256_with_key_states<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    key: &HmacSha256KeyStates,
+    msg: &[BoolTarget],
+) -> [U32Target; 8] {
+    let total_len_in_bits = (HMAC_SHA256_BLOCK_BYTES * 8 + msg.len()) as u64;
+    let total_len = [
+        builder.constant_u32((total_len_in_bits >> 32) as u32),
+        builder.constant_u32(total_len_in_bits as u32),
+    ];
+    let (_, inner) = hash_suffix(builder, key.inner, total_len, msg);
+    outer_hash(builder, key, inner)
+}
+
+/// HMAC-SHA256 of `msg` under `key`, both given as big-endian bits; see
+/// `hmac_sha256_key_states`.
+pub fn hmac_sha256<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    key: &[BoolTarget],
+    msg: &[BoolTarget],
+) -> [U32Target; 8] {
+    let key = hmac_sha256_key_states(builder, key);
+    hmac_sha256_with_key_states(builder, &key, msg)
+}
+
+/// Builds an HMAC-SHA256 circuit for a `key_len_in_bytes`-byte key and a
+/// `msg_len_in_bits`-bit message.
+pub fn make_hmac_sha256_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    key_len_in_bytes: usize,
+    msg_len_in_bits: u64,
+) -> HmacSha256Targets {
+    let key = add_virtual_bytes(builder, key_len_in_bytes);
+    let message: Vec<_> = (0..msg_len_in_bits)
+        .map(|_| builder.add_virtual_bool_target_unsafe())
+        .collect();
+    let mac = hmac_sha256(builder, &key, &message);
+
+    HmacSha256Targets { key, message, mac }
+}
+
+/// Builds an HMAC-SHA256 circuit for a `key_len_in_bytes`-byte key and a
+/// message whose padding (as part of the inner hash) fits in `max_total_bits`
+/// bits. As in `make_variable_length_circuits`, the padding is checked
+/// in-circuit against `msg_len` and `msg_blocks`, which do not count the key
+/// block.
+pub fn make_variable_length_hmac_sha256_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    key_len_in_bytes: usize,
+    max_total_bits: usize,
+) -> VariableLengthHmacSha256Targets {
+    let key = add_virtual_bytes(builder, key_len_in_bytes);
+    let key_states = hmac_sha256_key_states(builder, &key);
+    let inner = make_variable_length_suffix_circuits(builder, key_states.inner, 1, max_total_bits);
+
+    VariableLengthHmacSha256Targets {
+        key,
+        message: inner.message,
+        mac: outer_hash(builder, &key_states, inner.digest),
+        msg_len: inner.msg_len,
+        msg_blocks: inner.msg_blocks,
+    }
+}
+
+pub fn fill_hmac_sha256_circuits<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    key: &[u8],
+    msg: &[u8],
+    targets: &HmacSha256Targets,
+) -> Result<()> {
+    fill_bytes(pw, key, &targets.key)?;
+    fill_bytes(pw, msg, &targets.message)
+}
+
+pub fn fill_variable_length_hmac_sha256_circuits<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    key: &[u8],
+    msg: &[u8],
+    max_total_bits: usize,
+    targets: &VariableLengthHmacSha256Targets,
+) -> Result<()> {
+    fill_bytes(pw, key, &targets.key)?;
+    fill_variable_length_message(
+        pw,
+        msg,
+        HMAC_SHA256_BLOCK_BYTES * 8,
+        max_total_bits,
+        &targets.message,
+        targets.msg_len,
+        targets.msg_blocks,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        iop::witness::PartialWitness,
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+
+    use crate::circuit::digest_from_proof;
+    use crate::hmac::{
+        fill_hmac_sha256_circuits, fill_variable_length_hmac_sha256_circuits, hmac_sha256_native,
+        make_hmac_sha256_circuits, make_variable_length_hmac_sha256_circuits,
+    };
+    use crate::test_util::hex;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// RFC 4231 test cases 1 to 7 as `(key, message, HMAC-SHA256)`. Case 5 is
+    /// truncated to 128 bits in the RFC.
+    fn rfc4231_vectors() -> Vec<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        [
+            (
+                "0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b",
+                b"Hi There".to_vec(),
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                "4a656665",
+                b"what do ya want for nothing?".to_vec(),
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+                vec![0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                "0102030405060708090a0b0c0d0e0f10111213141516171819",
+                vec![0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            (
+                "0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c",
+                b"Test With Truncation".to_vec(),
+                "a3b6167473100ee06e0c796c2955552b",
+            ),
+            (
+                &"aa".repeat(131),
+                b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                &"aa".repeat(131),
+                b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.".to_vec(),
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ]
+        .into_iter()
+        .map(|(key, msg, mac)| (hex(key), msg, hex(mac)))
+        .collect()
+    }
+
+    #[test]
+    fn test_hmac_sha256_native() {
+        for (key, msg, mac) in rfc4231_vectors() {
+            assert_eq!(hmac_sha256_native(&key, &msg)[..mac.len()], mac);
+        }
+    }
+
+    #[test]
+    fn test_hmac_sha256_circuit() -> anyhow::Result<()> {
+        // A short key, a key of 25 bytes and a key longer than a block.
+        let vectors = rfc4231_vectors();
+        for (key, msg, mac) in [&vectors[0], &vectors[3], &vectors[6]] {
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let targets = make_hmac_sha256_circuits(&mut builder, key.len(), msg.len() as u64 * 8);
+            for word in &targets.mac {
+                builder.register_public_input(word.0);
+            }
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            fill_hmac_sha256_circuits(&mut pw, key, msg, &targets)?;
+            let proof = data.prove(pw)?;
+            assert_eq!(digest_from_proof(&proof)[..], mac[..]);
+            data.verify(proof)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_variable_length_hmac_sha256_circuit() -> anyhow::Result<()> {
+        let vectors = rfc4231_vectors();
+        // Test cases 1, 3 and 5 share a 20-byte key, and 6 and 7 a 131-byte
+        // one; each group is proven with a single circuit.
+        for (group, max_total_bits) in [(&[0, 2, 4][..], 1024), (&[5, 6][..], 512 * 3)] {
+            let key_len = vectors[group[0]].0.len();
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let targets =
+                make_variable_length_hmac_sha256_circuits(&mut builder, key_len, max_total_bits);
+            for word in &targets.mac {
+                builder.register_public_input(word.0);
+            }
+            let data = builder.build::<C>();
+
+            for &i in group {
+                let (key, msg, mac) = &vectors[i];
+                let mut pw = PartialWitness::new();
+                fill_variable_length_hmac_sha256_circuits(
+                    &mut pw,
+                    key,
+                    msg,
+                    max_total_bits,
+                    &targets,
+                )?;
+                let proof = data.prove(pw)?;
+                assert_eq!(digest_from_proof(&proof)[..mac.len()], mac[..]);
+                data.verify(proof)?;
+            }
+        }
+        Ok(())
+    }
+}
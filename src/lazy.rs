@@ -0,0 +1,211 @@
+use plonky2::{
+    field::extension::Extendable, hash::hash_types::RichField, iop::target::BoolTarget,
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_u32::gadgets::arithmetic_u32::U32Target;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::circuit::{bits_to_u32_target, u32_to_bits_target};
+
+#[derive(Debug)]
+struct LazyU32WithBitsInner {
+    u32_target: Option<U32Target>,
+    bits: Option<Vec<BoolTarget>>,
+}
+
+/// A 32-bit word held as a `U32Target`, as 32 big-endian bits, or both. The
+/// missing form is computed on first use and cached; clones share the cache,
+/// so a word is decomposed (or recomposed) at most once.
+///
+/// The builder is passed to the getters rather than stored, so a word must
+/// only be used with the builder it was created for.
+#[derive(Clone, Debug)]
+pub struct LazyU32WithBits {
+    inner: Rc<RefCell<LazyU32WithBitsInner>>,
+}
+
+impl LazyU32WithBits {
+    /// Create from a U32Target (bits will be computed lazily)
+    pub fn from_u32(u32_target: U32Target) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(LazyU32WithBitsInner {
+                u32_target: Some(u32_target),
+                bits: None,
+            })),
+        }
+    }
+
+    /// Create from big-endian bits (u32 will be computed lazily)
+    pub fn from_bits(bits: Vec<BoolTarget>) -> Self {
+        assert_eq!(bits.len(), 32);
+        Self {
+            inner: Rc::new(RefCell::new(LazyU32WithBitsInner {
+                u32_target: None,
+                bits: Some(bits),
+            })),
+        }
+    }
+
+    /// Create from a U32Target and its big-endian bits, which the caller has
+    /// already constrained to agree
+    pub fn from_u32_and_bits(u32_target: U32Target, bits: Vec<BoolTarget>) -> Self {
+        assert_eq!(bits.len(), 32);
+        Self {
+            inner: Rc::new(RefCell::new(LazyU32WithBitsInner {
+                u32_target: Some(u32_target),
+                bits: Some(bits),
+            })),
+        }
+    }
+
+    /// Get the U32Target, computing it from bits if necessary
+    pub fn get_u32<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> U32Target {
+        let mut inner = self.inner.borrow_mut();
+        match inner.u32_target {
+            Some(u32) => u32,
+            None => {
+                let bits = inner.bits.clone().unwrap();
+                let u32_target = bits_to_u32_target(builder, bits);
+                inner.u32_target = Some(u32_target);
+                u32_target
+            }
+        }
+    }
+
+    /// Get the big-endian bits, computing them from U32 if necessary
+    pub fn get_bits<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> Vec<BoolTarget> {
+        let mut inner = self.inner.borrow_mut();
+        match &inner.bits {
+            Some(bits) => bits.clone(),
+            None => {
+                let u32_target = inner.u32_target.unwrap();
+                let bits = u32_to_bits_target::<F, D, 2>(builder, &u32_target);
+                inner.bits = Some(bits.clone());
+                bits
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LazyU64WithBitsInner {
+    /// Big-endian halves `[hi, lo]`.
+    u32_targets: Option<[U32Target; 2]>,
+    bits: Option<Vec<BoolTarget>>,
+}
+
+/// 64-bit counterpart of `LazyU32WithBits`, held as two U32 halves or 64
+/// big-endian bits.
+#[derive(Clone, Debug)]
+pub struct LazyU64WithBits {
+    inner: Rc<RefCell<LazyU64WithBitsInner>>,
+}
+
+impl LazyU64WithBits {
+    /// Create from big-endian U32 halves `[hi, lo]` (bits will be computed lazily)
+    pub fn from_u32s(u32_targets: [U32Target; 2]) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(LazyU64WithBitsInner {
+                u32_targets: Some(u32_targets),
+                bits: None,
+            })),
+        }
+    }
+
+    /// Create from big-endian bits (U32 halves will be computed lazily)
+    pub fn from_bits(bits: Vec<BoolTarget>) -> Self {
+        assert_eq!(bits.len(), 64);
+        Self {
+            inner: Rc::new(RefCell::new(LazyU64WithBitsInner {
+                u32_targets: None,
+                bits: Some(bits),
+            })),
+        }
+    }
+
+    /// Get the U32 halves `[hi, lo]`, computing them from bits if necessary
+    pub fn get_u32s<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> [U32Target; 2] {
+        let mut inner = self.inner.borrow_mut();
+        match inner.u32_targets {
+            Some(u32s) => u32s,
+            None => {
+                let bits = inner.bits.as_ref().unwrap();
+                let hi = bits_to_u32_target(builder, bits[..32].to_vec());
+                let lo = bits_to_u32_target(builder, bits[32..].to_vec());
+                inner.u32_targets = Some([hi, lo]);
+                [hi, lo]
+            }
+        }
+    }
+
+    /// Get the bits, computing them from the U32 halves if necessary
+    pub fn get_bits<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> Vec<BoolTarget> {
+        let mut inner = self.inner.borrow_mut();
+        match &inner.bits {
+            Some(bits) => bits.clone(),
+            None => {
+                let [hi, lo] = inner.u32_targets.unwrap();
+                let mut bits = u32_to_bits_target::<F, D, 2>(builder, &hi);
+                bits.extend(u32_to_bits_target::<F, D, 2>(builder, &lo));
+                inner.bits = Some(bits.clone());
+                bits
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        iop::witness::PartialWitness,
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+    use plonky2_u32::{gadgets::arithmetic_u32::CircuitBuilderU32, witness::WitnessU32};
+
+    use crate::lazy::LazyU32WithBits;
+
+    #[test]
+    fn test_lazy_u32_decomposes_once() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let x = builder.add_virtual_u32_target();
+        let lazy = LazyU32WithBits::from_u32(x);
+        let bits = lazy.get_bits(&mut builder);
+        let num_gates = builder.num_gates();
+        // A clone shares the decomposition, and recomposing returns the
+        // original target.
+        assert_eq!(lazy.clone().get_bits(&mut builder), bits);
+        assert_eq!(lazy.get_u32(&mut builder).0, x.0);
+        assert_eq!(builder.num_gates(), num_gates);
+
+        // Recomposing bits yields the same word.
+        let y = LazyU32WithBits::from_bits(bits).get_u32(&mut builder);
+        builder.connect_u32(x, y);
+
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_u32_target(x, 0xdeadbeef)?;
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+}
@@ -1,3 +1,19 @@
+pub mod cache;
 pub mod circuit;
+pub mod config;
+pub mod estimate;
 pub mod gadgets;
 pub mod gates;
+pub mod hkdf;
+pub mod hmac;
+pub mod lazy;
+pub mod lookup;
+pub mod merkle;
+pub mod pbkdf2;
+pub mod recursion;
+pub mod serialization;
+pub mod sha512;
+pub mod spread;
+
+#[cfg(test)]
+pub(crate) mod test_util;
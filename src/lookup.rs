@@ -0,0 +1,441 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::{
+        generator::{GeneratedValues, SimpleGenerator},
+        target::{BoolTarget, Target},
+        witness::{PartitionWitness, Witness},
+    },
+    plonk::{circuit_builder::CircuitBuilder, circuit_data::CommonCircuitData},
+    util::serialization::{Buffer, IoResult, Read, Write},
+};
+use plonky2_u32::gadgets::arithmetic_u32::U32Target;
+
+use crate::circuit::Sha256RoundFunctions;
+
+/// Spread form of `x`: bit `i` moves to bit `2i`, so the spread forms of up
+/// to three bytes can be added without carries between bit positions.
+pub fn spread(x: u8) -> u16 {
+    (0..8).fold(0, |acc, i| acc | (((x as u16) >> i) & 1) << (2 * i))
+}
+
+/// Inverse of `spread` on the even bits of `s`.
+fn even_bits(s: u16) -> u16 {
+    (0..8).fold(0, |acc, i| acc | ((s >> (2 * i)) & 1) << i)
+}
+
+/// A rotation or logical shift to the right by some number of bits.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Shift {
+    Rotr(usize),
+    Shr(usize),
+}
+
+impl Shift {
+    /// Shifts the bits of a word, least significant first, filling in `zero`.
+    pub(crate) fn apply(self, bits: &[BoolTarget], zero: BoolTarget) -> Vec<BoolTarget> {
+        let n = bits.len();
+        (0..n)
+            .map(|i| match self {
+                Shift::Rotr(r) => bits[(i + r) % n],
+                Shift::Shr(r) if i + r < n => bits[i + r],
+                Shift::Shr(_) => zero,
+            })
+            .collect()
+    }
+}
+
+/// The lookup backend. Words are split into limbs of at most 8 bits, whose
+/// spread forms come from lookup tables (which also range-check them).
+///
+/// In spread form, each bit of a byte gets a 2-bit slot, and adding up to three
+/// spread bytes counts the ones in every slot. XOR is then the low bit of each
+/// slot and majority the high bit, both read back with a 2^16-entry table.
+/// Rotations and shifts only reorder limbs: the limb boundaries are chosen so
+/// that no limb straddles an output byte.
+///
+/// The tables are added to the builder on first use, so one instance should be
+/// used for all the blocks of a circuit.
+#[derive(Debug, Default)]
+pub struct LookupRoundFunctions {
+    /// Spread tables for limbs of 1 to 8 bits.
+    spread_tables: [Option<usize>; 8],
+    even_table: Option<usize>,
+    odd_table: Option<usize>,
+    /// Spread forms of the bytes (least significant first) of words seen so
+    /// far, keyed by the word's target.
+    byte_spreads: HashMap<Target, [Target; 4]>,
+}
+
+impl LookupRoundFunctions {
+    fn spread_table<F: RichField + Extendable<D>, const D: usize>(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        bits: usize,
+    ) -> usize {
+        *self.spread_tables[bits - 1].get_or_insert_with(|| {
+            let table = (0..1u16 << bits).map(|x| (x, spread(x as u8))).collect();
+            builder.add_lookup_table_from_pairs(Arc::new(table))
+        })
+    }
+
+    fn even_table<F: RichField + Extendable<D>, const D: usize>(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> usize {
+        *self.even_table.get_or_insert_with(|| {
+            let table = (0..=u16::MAX).map(|s| (s, even_bits(s))).collect();
+            builder.add_lookup_table_from_pairs(Arc::new(table))
+        })
+    }
+
+    fn odd_table<F: RichField + Extendable<D>, const D: usize>(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> usize {
+        *self.odd_table.get_or_insert_with(|| {
+            let table = (0..=u16::MAX).map(|s| (s, even_bits(s >> 1))).collect();
+            builder.add_lookup_table_from_pairs(Arc::new(table))
+        })
+    }
+
+    /// Splits `x` into limbs starting at the ascending bit positions
+    /// `boundaries` (the first being 0) and returns their spread forms. The
+    /// lookups range-check the limbs, which then recompose to `x`.
+    fn spread_limbs<F: RichField + Extendable<D>, const D: usize>(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        x: Target,
+        boundaries: &[usize],
+    ) -> Vec<Target> {
+        let limbs = builder.add_virtual_targets(boundaries.len());
+        builder.add_simple_generator(LimbSplitGenerator {
+            x,
+            limbs: limbs.clone(),
+            boundaries: boundaries.to_vec(),
+        });
+
+        let mut sum = builder.zero();
+        let mut spreads = Vec::new();
+        for (i, &limb) in limbs.iter().enumerate() {
+            let lo = boundaries[i];
+            let hi = boundaries.get(i + 1).copied().unwrap_or(32);
+            let table = self.spread_table(builder, hi - lo);
+            spreads.push(builder.add_lookup_from_index(limb, table));
+            sum = builder.mul_const_add(F::from_canonical_u64(1 << lo), limb, sum);
+        }
+        builder.connect(sum, x);
+        spreads
+    }
+
+    fn byte_spreads<F: RichField + Extendable<D>, const D: usize>(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        x: U32Target,
+    ) -> [Target; 4] {
+        if let Some(spreads) = self.byte_spreads.get(&x.0) {
+            return *spreads;
+        }
+        let spreads = self.spread_limbs(builder, x.0, &[0, 8, 16, 24]);
+        let spreads = [spreads[0], spreads[1], spreads[2], spreads[3]];
+        self.byte_spreads.insert(x.0, spreads);
+        spreads
+    }
+
+    /// XOR of three rotations or shifts of `x`. With `cache_bytes`, the spread
+    /// forms of the bytes of `x` are also derived from the limbs, for a later
+    /// `ch` or `maj` of the same word.
+    fn xor3_shifts<F: RichField + Extendable<D>, const D: usize>(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        x: U32Target,
+        shifts: [Shift; 3],
+        cache_bytes: bool,
+    ) -> U32Target {
+        // Input bit `8k + r` lands on the output byte boundary `8k`.
+        let mut boundaries = vec![0, 8, 16, 24];
+        for shift in shifts {
+            for k in 0..4 {
+                match shift {
+                    Shift::Rotr(r) => boundaries.push((8 * k + r) % 32),
+                    Shift::Shr(r) if 8 * k + r < 32 => boundaries.push(8 * k + r),
+                    Shift::Shr(_) => {}
+                }
+            }
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        let spreads = self.spread_limbs(builder, x.0, &boundaries);
+
+        let zero = builder.zero();
+        let mut chunks = [zero; 4];
+        for shift in shifts {
+            for (&lo, &spread) in boundaries.iter().zip(&spreads) {
+                let pos = match shift {
+                    Shift::Rotr(r) => (lo + 32 - r) % 32,
+                    Shift::Shr(r) if lo >= r => lo - r,
+                    Shift::Shr(_) => continue,
+                };
+                let c = pos / 8;
+                let weight = F::from_canonical_u64(1 << (2 * (pos % 8)));
+                chunks[c] = builder.mul_const_add(weight, spread, chunks[c]);
+            }
+        }
+
+        let even_table = self.even_table(builder);
+        let mut out = zero;
+        for (c, &chunk) in chunks.iter().enumerate() {
+            let byte = builder.add_lookup_from_index(chunk, even_table);
+            out = builder.mul_const_add(F::from_canonical_u64(1 << (8 * c)), byte, out);
+        }
+
+        if cache_bytes && !self.byte_spreads.contains_key(&x.0) {
+            let mut bytes = [zero; 4];
+            for (&lo, &spread) in boundaries.iter().zip(&spreads) {
+                let weight = F::from_canonical_u64(1 << (2 * (lo % 8)));
+                bytes[lo / 8] = builder.mul_const_add(weight, spread, bytes[lo / 8]);
+            }
+            self.byte_spreads.insert(x.0, bytes);
+        }
+
+        U32Target(out)
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Sha256RoundFunctions<F, D>
+    for LookupRoundFunctions
+{
+    type Word = U32Target;
+
+    fn u32_to_word(&mut self, _builder: &mut CircuitBuilder<F, D>, x: U32Target) -> Self::Word {
+        x
+    }
+
+    fn word_to_u32(&mut self, _builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> U32Target {
+        *x
+    }
+
+    fn big_sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        let shifts = [Shift::Rotr(2), Shift::Rotr(13), Shift::Rotr(22)];
+        self.xor3_shifts(builder, *x, shifts, true)
+    }
+
+    fn big_sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        let shifts = [Shift::Rotr(6), Shift::Rotr(11), Shift::Rotr(25)];
+        self.xor3_shifts(builder, *x, shifts, true)
+    }
+
+    fn sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        let shifts = [Shift::Rotr(7), Shift::Rotr(18), Shift::Shr(3)];
+        self.xor3_shifts(builder, *x, shifts, false)
+    }
+
+    fn sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        let shifts = [Shift::Rotr(17), Shift::Rotr(19), Shift::Shr(10)];
+        self.xor3_shifts(builder, *x, shifts, false)
+    }
+
+    // ch = (e & f) + (!e & g): the two terms never overlap, and each is the
+    // high bit of a two-term spread sum, with spread(!e) = 0x5555 - spread(e).
+    fn ch(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        e: &Self::Word,
+        f: &Self::Word,
+        g: &Self::Word,
+    ) -> Self::Word {
+        let e = self.byte_spreads(builder, *e);
+        let f = self.byte_spreads(builder, *f);
+        let g = self.byte_spreads(builder, *g);
+        let odd_table = self.odd_table(builder);
+
+        let mut out = builder.zero();
+        for c in 0..4 {
+            let weight = F::from_canonical_u64(1 << (8 * c));
+            let e_plus_f = builder.add(e[c], f[c]);
+            let e_and_f = builder.add_lookup_from_index(e_plus_f, odd_table);
+            let g_minus_e = builder.sub(g[c], e[c]);
+            let not_e_plus_g = builder.add_const(g_minus_e, F::from_canonical_u64(0x5555));
+            let not_e_and_g = builder.add_lookup_from_index(not_e_plus_g, odd_table);
+            out = builder.mul_const_add(weight, e_and_f, out);
+            out = builder.mul_const_add(weight, not_e_and_g, out);
+        }
+        U32Target(out)
+    }
+
+    fn maj(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        a: &Self::Word,
+        b: &Self::Word,
+        c: &Self::Word,
+    ) -> Self::Word {
+        let a = self.byte_spreads(builder, *a);
+        let b = self.byte_spreads(builder, *b);
+        let c = self.byte_spreads(builder, *c);
+        let odd_table = self.odd_table(builder);
+
+        let mut out = builder.zero();
+        for i in 0..4 {
+            let sum = builder.add_many([a[i], b[i], c[i]]);
+            let byte = builder.add_lookup_from_index(sum, odd_table);
+            out = builder.mul_const_add(F::from_canonical_u64(1 << (8 * i)), byte, out);
+        }
+        U32Target(out)
+    }
+}
+
+/// Computes the limbs of `x` for `LookupRoundFunctions::spread_limbs`.
+#[derive(Debug, Default)]
+pub struct LimbSplitGenerator {
+    x: Target,
+    limbs: Vec<Target>,
+    boundaries: Vec<usize>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for LimbSplitGenerator {
+    fn id(&self) -> String {
+        "LimbSplitGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        vec![self.x]
+    }
+
+    fn run_once(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+    ) -> Result<()> {
+        let x = witness.get_target(self.x).to_canonical_u64();
+        for (i, &limb) in self.limbs.iter().enumerate() {
+            let lo = self.boundaries[i];
+            let hi = self.boundaries.get(i + 1).copied().unwrap_or(32);
+            let value = (x >> lo) & ((1 << (hi - lo)) - 1);
+            out_buffer.set_target(limb, F::from_canonical_u64(value))?;
+        }
+        Ok(())
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_target(self.x)?;
+        dst.write_target_vec(&self.limbs)?;
+        dst.write_usize_vec(&self.boundaries)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let x = src.read_target()?;
+        let limbs = src.read_target_vec()?;
+        let boundaries = src.read_usize_vec()?;
+        Ok(Self {
+            x,
+            limbs,
+            boundaries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+    use plonky2_u32::{gadgets::arithmetic_u32::CircuitBuilderU32, witness::WitnessU32};
+    use sha2::Digest;
+
+    use crate::circuit::{
+        array_to_bits, make_circuits_u32_with_backend, sha256_compress_native,
+        sha256_compress_with, Sha256Backend, EXAMPLE_MESSAGE, H256,
+    };
+    use crate::lookup::LookupRoundFunctions;
+
+    #[test]
+    fn test_lookup_compress() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        // All-ones words exercise the top spread slots and the `!e` term.
+        let block: [u32; 16] = core::array::from_fn(|i| match i {
+            0..=3 => u32::MAX,
+            _ => (i as u32).wrapping_mul(0x9e3779b9),
+        });
+        let state = builder.add_virtual_u32_targets(8);
+        let words = builder.add_virtual_u32_targets(16);
+        builder.range_check_u32(state.clone());
+        builder.range_check_u32(words.clone());
+        let out = sha256_compress_with(
+            &mut builder,
+            &mut LookupRoundFunctions::default(),
+            state.clone().try_into().unwrap(),
+            words.clone().try_into().unwrap(),
+        );
+        let expected = sha256_compress_native(H256, block);
+        for (o, e) in out.iter().zip(expected) {
+            let e = builder.constant_u32(e);
+            builder.connect_u32(*o, e);
+        }
+
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        for (t, v) in state.iter().zip(H256) {
+            pw.set_u32_target(*t, v)?;
+        }
+        for (t, v) in words.iter().zip(block) {
+            pw.set_u32_target(*t, v)?;
+        }
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_lookup_backend_against_bits() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let msg = &EXAMPLE_MESSAGE[..1000];
+        let digest = sha2::Sha256::digest(msg);
+        let mut num_gates = Vec::new();
+        for backend in [Sha256Backend::Bits, Sha256Backend::Lookup] {
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let targets =
+                make_circuits_u32_with_backend(&mut builder, (msg.len() * 8) as u64, backend);
+            for (i, word) in targets.digest.iter().enumerate() {
+                let expected = u32::from_be_bytes(digest[4 * i..4 * i + 4].try_into().unwrap());
+                let expected = builder.constant_u32(expected);
+                builder.connect_u32(*word, expected);
+            }
+            num_gates.push(builder.num_gates());
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            for (target, bit) in targets.message.iter().zip(array_to_bits(msg)) {
+                pw.set_bool_target(*target, bit)?;
+            }
+            let proof = data.prove(pw)?;
+            data.verify(proof)?;
+        }
+
+        // Lookups are cheap, but every limb is also recomposed with an
+        // `ArithmeticGate` op, once per shift, and 20 of those fill a row. The
+        // bit backend fits a whole σ in one or two rows, so it stays smaller
+        // even before the lookup tables are laid out.
+        let [bits, lookup] = num_gates[..] else {
+            unreachable!()
+        };
+        assert!(lookup > bits, "{num_gates:?}");
+        Ok(())
+    }
+}
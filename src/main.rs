@@ -1,13 +1,16 @@
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use log::{Level, LevelFilter};
 use plonky2::iop::witness::{PartialWitness, WitnessWrite};
-use plonky2::plonk::circuit_builder::CircuitBuilder;
-use plonky2::plonk::circuit_data::CircuitConfig;
 use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
 use plonky2::util::timing::TimingTree;
-use plonky2_sha256::circuit::{array_to_bits, make_circuits};
+use plonky2_sha256::cache::{Sha256CircuitCache, Sha256CircuitShape, Sha256CircuitTargets};
+use plonky2_sha256::circuit::{array_to_bits, digest_from_proof, PublicMessage};
+use plonky2_sha256::config::Sha256ConfigPreset;
 use sha2::{Digest, Sha256};
 
+/// Built circuits are kept here between runs.
+const CIRCUIT_CACHE_DIR: &str = "circuit-cache";
+
 pub fn prove_sha256(msg: &[u8]) -> Result<()> {
     let mut hasher = Sha256::new();
     hasher.update(msg);
@@ -20,33 +23,41 @@ pub fn prove_sha256(msg: &[u8]) -> Result<()> {
     const D: usize = 2;
     type C = PoseidonGoldilocksConfig;
     type F = <C as GenericConfig<D>>::F;
-    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
-    let targets = make_circuits(&mut builder, len as u64);
+    let preset = Sha256ConfigPreset::fastest_for::<F, D>(len as u64);
+    println!("config preset: {:?}", preset);
+    let timing = TimingTree::new("load or build circuit", Level::Debug);
+    let circuit = Sha256CircuitCache::new(CIRCUIT_CACHE_DIR).load_or_build::<F, C, D>(
+        &preset.config(),
+        Sha256CircuitShape::FixedLength {
+            msg_len_in_bits: len as u64,
+            public_message: PublicMessage::None,
+        },
+    )?;
+    timing.print();
+    let Sha256CircuitTargets::FixedLength(targets) = &circuit.targets else {
+        unreachable!("fixed-length shape")
+    };
+    let data = &circuit.data;
     let mut pw = PartialWitness::new();
 
     for (i, msg_bit) in msg_bits.iter().enumerate().take(len) {
         pw.set_bool_target(targets.message[i], *msg_bit)?;
     }
 
-    let expected_res = array_to_bits(hash.as_slice());
-    for (i, expected_res_bit) in expected_res.iter().enumerate() {
-        if *expected_res_bit {
-            builder.assert_one(targets.digest[i].target);
-        } else {
-            builder.assert_zero(targets.digest[i].target);
-        }
-    }
-
     println!(
-        "Constructing inner proof with {} gates",
-        builder.num_gates()
+        "Constructing inner proof with 2^{} rows",
+        data.common.degree_bits()
     );
-    builder.print_gate_counts(0);
-    let data = builder.build::<C>();
     let timing = TimingTree::new("prove", Level::Debug);
     let proof = data.prove(pw).unwrap();
     timing.print();
 
+    // The digest is a public input rather than a constant of the circuit.
+    ensure!(
+        digest_from_proof(&proof) == hash.as_slice(),
+        "proof does not commit to the expected digest"
+    );
+
     let timing = TimingTree::new("verify", Level::Debug);
     let res = data.verify(proof);
     timing.print();
@@ -0,0 +1,930 @@
+use anyhow::Result;
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::{target::BoolTarget, witness::PartialWitness, witness::WitnessWrite},
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_u32::{
+    gadgets::arithmetic_u32::{CircuitBuilderU32, U32Target},
+    witness::WitnessU32,
+};
+use sha2::{Digest, Sha256};
+use std::ops::Range;
+
+use crate::circuit::{
+    add_virtual_bytes, array_to_bits, digest_to_bits, fill_bytes, hash_suffix, sha256_compress,
+    H256,
+};
+
+/// How the leaves and nodes of a SHA-256 Merkle tree are hashed, and how
+/// trees whose size is not a power of two are shaped.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MerkleHashOptions {
+    /// Byte hashed before the leaf data.
+    pub leaf_prefix: Option<u8>,
+    /// Byte hashed before the two children of a node.
+    pub node_prefix: Option<u8>,
+    /// Hash every leaf and node twice (SHA-256d).
+    pub double_hash: bool,
+    /// Pair nodes level by level and hash the last node of an odd level with
+    /// itself, instead of splitting at the largest power of two as RFC 6962
+    /// does. This only changes how the native helpers lay out a tree: a path
+    /// through a duplicated node has the node itself as its sibling.
+    pub duplicate_last_node: bool,
+}
+
+impl MerkleHashOptions {
+    /// RFC 6962 (certificate transparency): `SHA-256(0x00 || leaf)` and
+    /// `SHA-256(0x01 || left || right)`.
+    pub const RFC6962: Self = Self {
+        leaf_prefix: Some(0x00),
+        node_prefix: Some(0x01),
+        double_hash: false,
+        duplicate_last_node: false,
+    };
+    /// `SHA-256(leaf)` and `SHA-256(left || right)`. Without domain separation
+    /// a node can pass for a 64-byte leaf, so leaves should not be 64 bytes.
+    pub const UNPREFIXED: Self = Self {
+        leaf_prefix: None,
+        node_prefix: None,
+        double_hash: false,
+        duplicate_last_node: false,
+    };
+    /// Bitcoin transaction trees: `SHA-256d(left || right)`, with the last
+    /// node of an odd level paired with itself. The leaves are txids in
+    /// internal byte order, i.e. already hashed, so start from
+    /// `merkle_root_from_leaf_hash` and the `*_from_leaf_hashes` helpers.
+    pub const BITCOIN: Self = Self {
+        leaf_prefix: None,
+        node_prefix: None,
+        double_hash: true,
+        duplicate_last_node: true,
+    };
+}
+
+fn prefix_bits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    prefix: Option<u8>,
+) -> Vec<BoolTarget> {
+    array_to_bits(prefix.as_slice())
+        .into_iter()
+        .map(|bit| builder.constant_bool(bit))
+        .collect()
+}
+
+/// Hashes `message` from the IV, given as big-endian bits.
+fn sha256_bits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    message: &[BoolTarget],
+) -> [U32Target; 8] {
+    let iv = H256.map(|c| builder.constant_u32(c));
+    let len = message.len() as u64;
+    let total_len = [
+        builder.constant_u32((len >> 32) as u32),
+        builder.constant_u32(len as u32),
+    ];
+    hash_suffix(builder, iv, total_len, message).1
+}
+
+/// With `options.double_hash`, hashes the 32-byte `digest` once more.
+fn finish_hash<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    digest: [U32Target; 8],
+    options: MerkleHashOptions,
+) -> [U32Target; 8] {
+    if !options.double_hash {
+        return digest;
+    }
+    let iv = H256.map(|c| builder.constant_u32(c));
+    let zero = builder.zero_u32();
+    let mut block = digest.to_vec();
+    block.push(builder.constant_u32(0x80000000));
+    block.resize(15, zero);
+    block.push(builder.constant_u32(256));
+    sha256_compress(builder, iv, block.try_into().unwrap())
+}
+
+/// Hash of a leaf given as big-endian bits, constrained to be boolean here.
+pub fn merkle_leaf_hash<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    leaf: &[BoolTarget],
+    options: MerkleHashOptions,
+) -> [U32Target; 8] {
+    let mut message = prefix_bits(builder, options.leaf_prefix);
+    message.extend_from_slice(leaf);
+    let digest = sha256_bits(builder, &message);
+    finish_hash(builder, digest, options)
+}
+
+/// Hash of an inner node from its children's hashes. Without a prefix the
+/// children fill a block as they are; with one they are split into bits.
+/// With `double_hash`, the digest is hashed once more.
+pub fn merkle_node_hash<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    left: [U32Target; 8],
+    right: [U32Target; 8],
+    options: MerkleHashOptions,
+) -> [U32Target; 8] {
+    let digest = if options.node_prefix.is_some() {
+        let mut message = prefix_bits(builder, options.node_prefix);
+        message.extend(digest_to_bits(builder, &left));
+        message.extend(digest_to_bits(builder, &right));
+        sha256_bits(builder, &message)
+    } else {
+        let iv = H256.map(|c| builder.constant_u32(c));
+        let children: Vec<_> = left.into_iter().chain(right).collect();
+        let state = sha256_compress(builder, iv, children.try_into().unwrap());
+        // The second block is all padding: the `1` bit, zero fill and 512.
+        let zero = builder.zero_u32();
+        let mut padding = vec![builder.constant_u32(0x80000000)];
+        padding.resize(15, zero);
+        padding.push(builder.constant_u32(512));
+        sha256_compress(builder, state, padding.try_into().unwrap())
+    };
+    finish_hash(builder, digest, options)
+}
+
+/// Recomputes the root from a leaf hash and its authentication path, leaf
+/// first. `directions[i]` is true when the node at level `i` is a right child,
+/// i.e. `siblings[i]` goes on the left. The sibling words must be range
+/// checked and the directions boolean.
+pub fn merkle_root_from_leaf_hash<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    leaf_hash: [U32Target; 8],
+    siblings: &[[U32Target; 8]],
+    directions: &[BoolTarget],
+    options: MerkleHashOptions,
+) -> [U32Target; 8] {
+    assert_eq!(siblings.len(), directions.len());
+    let mut node = leaf_hash;
+    for (sibling, &is_right) in siblings.iter().zip(directions) {
+        let left =
+            core::array::from_fn(|i| U32Target(builder.select(is_right, sibling[i].0, node[i].0)));
+        let right =
+            core::array::from_fn(|i| U32Target(builder.select(is_right, node[i].0, sibling[i].0)));
+        node = merkle_node_hash(builder, left, right, options);
+    }
+    node
+}
+
+/// Like `merkle_root_from_leaf_hash`, hashing the leaf data first.
+pub fn merkle_root<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    leaf: &[BoolTarget],
+    siblings: &[[U32Target; 8]],
+    directions: &[BoolTarget],
+    options: MerkleHashOptions,
+) -> [U32Target; 8] {
+    let leaf_hash = merkle_leaf_hash(builder, leaf, options);
+    merkle_root_from_leaf_hash(builder, leaf_hash, siblings, directions, options)
+}
+
+/// Native counterpart of `finish_hash`.
+fn finish_hash_native(hasher: Sha256, options: MerkleHashOptions) -> [u8; 32] {
+    let digest = hasher.finalize();
+    if options.double_hash {
+        Sha256::digest(digest).into()
+    } else {
+        digest.into()
+    }
+}
+
+pub fn merkle_leaf_hash_native(leaf: &[u8], options: MerkleHashOptions) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(options.leaf_prefix.as_slice());
+    hasher.update(leaf);
+    finish_hash_native(hasher, options)
+}
+
+pub fn merkle_node_hash_native(
+    left: &[u8; 32],
+    right: &[u8; 32],
+    options: MerkleHashOptions,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(options.node_prefix.as_slice());
+    hasher.update(left);
+    hasher.update(right);
+    finish_hash_native(hasher, options)
+}
+
+/// Split point of a tree of `n > 1` leaves as in RFC 6962: the largest power
+/// of two below `n`. Power-of-two trees come out complete.
+fn split_point(n: usize) -> usize {
+    1 << (n - 1).ilog2()
+}
+
+/// The level above `level` in a tree with `duplicate_last_node`.
+fn parent_level(level: &[[u8; 32]], options: MerkleHashOptions) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| merkle_node_hash_native(&pair[0], pair.last().unwrap(), options))
+        .collect()
+}
+
+/// Root of the tree over `leaves`, split as in RFC 6962 unless
+/// `options.duplicate_last_node` is set. An empty tree has the hash of the
+/// empty string as its root.
+pub fn merkle_root_native<L: AsRef<[u8]>>(leaves: &[L], options: MerkleHashOptions) -> [u8; 32] {
+    let leaf_hashes: Vec<_> = leaves
+        .iter()
+        .map(|leaf| merkle_leaf_hash_native(leaf.as_ref(), options))
+        .collect();
+    merkle_root_from_leaf_hashes_native(&leaf_hashes, options)
+}
+
+/// Like `merkle_root_native`, from the hashes of the leaves.
+pub fn merkle_root_from_leaf_hashes_native(
+    leaf_hashes: &[[u8; 32]],
+    options: MerkleHashOptions,
+) -> [u8; 32] {
+    match leaf_hashes {
+        [] => Sha256::digest([]).into(),
+        [leaf_hash] => *leaf_hash,
+        _ if options.duplicate_last_node => {
+            let mut level = leaf_hashes.to_vec();
+            while level.len() > 1 {
+                level = parent_level(&level, options);
+            }
+            level[0]
+        }
+        _ => {
+            let (left, right) = leaf_hashes.split_at(split_point(leaf_hashes.len()));
+            merkle_node_hash_native(
+                &merkle_root_from_leaf_hashes_native(left, options),
+                &merkle_root_from_leaf_hashes_native(right, options),
+                options,
+            )
+        }
+    }
+}
+
+/// Authentication path of a leaf, leaf first; see
+/// `merkle_root_from_leaf_hash` for the directions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub siblings: Vec<[u8; 32]>,
+    pub directions: Vec<bool>,
+}
+
+impl MerkleProof {
+    /// The path of leaf `index`. Leaves of trees whose size is not a power of
+    /// two can have shorter paths than others.
+    pub fn new<L: AsRef<[u8]>>(leaves: &[L], index: usize, options: MerkleHashOptions) -> Self {
+        let leaf_hashes: Vec<_> = leaves
+            .iter()
+            .map(|leaf| merkle_leaf_hash_native(leaf.as_ref(), options))
+            .collect();
+        Self::from_leaf_hashes(&leaf_hashes, index, options)
+    }
+
+    /// Like `new`, from the hashes of the leaves.
+    pub fn from_leaf_hashes(
+        leaf_hashes: &[[u8; 32]],
+        index: usize,
+        options: MerkleHashOptions,
+    ) -> Self {
+        assert!(
+            index < leaf_hashes.len(),
+            "leaf {} out of range for {} leaves",
+            index,
+            leaf_hashes.len()
+        );
+        let mut proof = Self {
+            siblings: Vec::new(),
+            directions: Vec::new(),
+        };
+        if options.duplicate_last_node {
+            let (mut level, mut index) = (leaf_hashes.to_vec(), index);
+            while level.len() > 1 {
+                // The last node of an odd level is its own sibling.
+                let sibling = level.get(index ^ 1).unwrap_or(&level[index]);
+                proof.siblings.push(*sibling);
+                proof.directions.push(index % 2 == 1);
+                level = parent_level(&level, options);
+                index /= 2;
+            }
+            return proof;
+        }
+
+        let (mut leaf_hashes, mut index) = (leaf_hashes, index);
+        // Walk down from the root, then put the path in leaf-first order.
+        while leaf_hashes.len() > 1 {
+            let (left, right) = leaf_hashes.split_at(split_point(leaf_hashes.len()));
+            let is_right = index >= left.len();
+            let sibling = if is_right { left } else { right };
+            proof
+                .siblings
+                .push(merkle_root_from_leaf_hashes_native(sibling, options));
+            proof.directions.push(is_right);
+            if is_right {
+                index -= left.len();
+                leaf_hashes = right;
+            } else {
+                leaf_hashes = left;
+            }
+        }
+        proof.siblings.reverse();
+        proof.directions.reverse();
+        proof
+    }
+
+    /// Native counterpart of `merkle_root`.
+    pub fn root(&self, leaf: &[u8], options: MerkleHashOptions) -> [u8; 32] {
+        self.root_from_leaf_hash(&merkle_leaf_hash_native(leaf, options), options)
+    }
+
+    /// Native counterpart of `merkle_root_from_leaf_hash`.
+    pub fn root_from_leaf_hash(
+        &self,
+        leaf_hash: &[u8; 32],
+        options: MerkleHashOptions,
+    ) -> [u8; 32] {
+        let mut node = *leaf_hash;
+        for (sibling, &is_right) in self.siblings.iter().zip(&self.directions) {
+            node = if is_right {
+                merkle_node_hash_native(sibling, &node, options)
+            } else {
+                merkle_node_hash_native(&node, sibling, options)
+            };
+        }
+        node
+    }
+}
+
+/// Allocates `n` range-checked digests, e.g. siblings.
+fn add_virtual_digests<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    n: usize,
+) -> Vec<[U32Target; 8]> {
+    let digests: Vec<[U32Target; 8]> = (0..n)
+        .map(|_| core::array::from_fn(|_| builder.add_virtual_u32_target()))
+        .collect();
+    builder.range_check_u32(digests.concat());
+    digests
+}
+
+fn fill_digests<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    digests: &[[u8; 32]],
+    targets: &[[U32Target; 8]],
+) -> Result<()> {
+    for (words, digest) in targets.iter().zip(digests) {
+        for (&target, bytes) in words.iter().zip(digest.chunks(4)) {
+            pw.set_u32_target(target, u32::from_be_bytes(bytes.try_into().unwrap()))?;
+        }
+    }
+    Ok(())
+}
+
+pub struct MerkleProofTargets {
+    /// Leaf data bits.
+    pub leaf: Vec<BoolTarget>,
+    /// Sibling hashes as eight big-endian words each, leaf first.
+    pub siblings: Vec<[U32Target; 8]>,
+    pub directions: Vec<BoolTarget>,
+    /// The root as eight big-endian words.
+    pub root: [U32Target; 8],
+}
+
+/// Builds a circuit recomputing the root from a `leaf_len_in_bytes`-byte leaf
+/// and a path of `depth` levels. Register the root as a public input to prove
+/// membership in a known tree.
+pub fn make_merkle_proof_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    leaf_len_in_bytes: usize,
+    depth: usize,
+    options: MerkleHashOptions,
+) -> MerkleProofTargets {
+    let leaf = add_virtual_bytes(builder, leaf_len_in_bytes);
+    let siblings = add_virtual_digests(builder, depth);
+    let directions: Vec<_> = (0..depth)
+        .map(|_| builder.add_virtual_bool_target_safe())
+        .collect();
+    let root = merkle_root(builder, &leaf, &siblings, &directions, options);
+
+    MerkleProofTargets {
+        leaf,
+        siblings,
+        directions,
+        root,
+    }
+}
+
+pub fn fill_merkle_proof_circuits<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    leaf: &[u8],
+    proof: &MerkleProof,
+    targets: &MerkleProofTargets,
+) -> Result<()> {
+    assert_eq!(
+        proof.siblings.len(),
+        targets.siblings.len(),
+        "path must have {} levels (got {})",
+        targets.siblings.len(),
+        proof.siblings.len()
+    );
+    fill_bytes(pw, leaf, &targets.leaf)?;
+    fill_digests(pw, &proof.siblings, &targets.siblings)?;
+    for (&target, &is_right) in targets.directions.iter().zip(&proof.directions) {
+        pw.set_bool_target(target, is_right)?;
+    }
+    Ok(())
+}
+
+/// One step of a multiproof check, in post-order: push a proven leaf (by its
+/// position among the proven leaves), push the next sibling (the root of the
+/// subtree of leaves `range`, which holds no proven leaf), or hash the top two
+/// nodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum MultiProofStep {
+    Leaf(usize),
+    Sibling(Range<usize>),
+    Node,
+}
+
+/// The steps that recompute the root of a tree of `num_leaves` leaves from
+/// the leaves at `indices`. Each node above a proven leaf is hashed once, no
+/// matter how many proven leaves lie below it.
+fn multiproof_steps(num_leaves: usize, indices: &[usize]) -> Vec<MultiProofStep> {
+    fn walk(
+        leaves: Range<usize>,
+        indices: &[usize],
+        first: usize,
+        steps: &mut Vec<MultiProofStep>,
+    ) {
+        if indices.is_empty() {
+            steps.push(MultiProofStep::Sibling(leaves));
+        } else if leaves.len() == 1 {
+            steps.push(MultiProofStep::Leaf(first));
+        } else {
+            let mid = leaves.start + split_point(leaves.len());
+            let (left, right) = indices.split_at(indices.partition_point(|&i| i < mid));
+            walk(leaves.start..mid, left, first, steps);
+            walk(mid..leaves.end, right, first + left.len(), steps);
+            steps.push(MultiProofStep::Node);
+        }
+    }
+
+    assert!(!indices.is_empty(), "a multiproof needs at least one leaf");
+    assert!(
+        indices.windows(2).all(|w| w[0] < w[1]),
+        "leaf indices must be increasing"
+    );
+    assert!(
+        indices.last() < Some(&num_leaves),
+        "leaf indices out of range for {} leaves",
+        num_leaves
+    );
+    let mut steps = Vec::new();
+    walk(0..num_leaves, indices, 0, &mut steps);
+    steps
+}
+
+fn assert_split_tree(options: MerkleHashOptions) {
+    assert!(
+        !options.duplicate_last_node,
+        "multiproofs need RFC 6962 shaped trees"
+    );
+}
+
+/// Runs `steps` on the proven leaf hashes and the siblings, returning the
+/// root.
+fn fold_multiproof<T: Copy>(
+    steps: &[MultiProofStep],
+    leaves: &[T],
+    siblings: &[T],
+    mut node: impl FnMut(T, T) -> T,
+) -> T {
+    let mut siblings = siblings.iter();
+    let mut stack = Vec::new();
+    for step in steps {
+        match step {
+            MultiProofStep::Leaf(i) => stack.push(leaves[*i]),
+            MultiProofStep::Sibling(_) => stack.push(*siblings.next().expect("too few siblings")),
+            MultiProofStep::Node => {
+                let right = stack.pop().unwrap();
+                let left = stack.pop().unwrap();
+                stack.push(node(left, right));
+            }
+        }
+    }
+    assert!(siblings.next().is_none(), "too many siblings");
+    stack.pop().unwrap()
+}
+
+/// Number of siblings in a multiproof for the leaves at `indices`.
+pub fn merkle_multiproof_num_siblings(num_leaves: usize, indices: &[usize]) -> usize {
+    multiproof_steps(num_leaves, indices)
+        .iter()
+        .filter(|step| matches!(step, MultiProofStep::Sibling(_)))
+        .count()
+}
+
+/// Proof that several leaves belong to one tree: the roots of the maximal
+/// subtrees without a proven leaf, left to right. Trees are split as in RFC
+/// 6962; `duplicate_last_node` is not supported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleMultiProof {
+    pub num_leaves: usize,
+    /// Increasing indices of the proven leaves.
+    pub indices: Vec<usize>,
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl MerkleMultiProof {
+    pub fn new<L: AsRef<[u8]>>(
+        leaves: &[L],
+        indices: &[usize],
+        options: MerkleHashOptions,
+    ) -> Self {
+        assert_split_tree(options);
+        let siblings = multiproof_steps(leaves.len(), indices)
+            .into_iter()
+            .filter_map(|step| match step {
+                MultiProofStep::Sibling(range) => Some(merkle_root_native(&leaves[range], options)),
+                _ => None,
+            })
+            .collect();
+        Self {
+            num_leaves: leaves.len(),
+            indices: indices.to_vec(),
+            siblings,
+        }
+    }
+
+    /// Native counterpart of `merkle_multiproof_root`, with `leaves` the data
+    /// of the proven leaves in the order of `indices`.
+    pub fn root<L: AsRef<[u8]>>(&self, leaves: &[L], options: MerkleHashOptions) -> [u8; 32] {
+        assert_eq!(leaves.len(), self.indices.len());
+        assert_split_tree(options);
+        let leaf_hashes: Vec<_> = leaves
+            .iter()
+            .map(|leaf| merkle_leaf_hash_native(leaf.as_ref(), options))
+            .collect();
+        let steps = multiproof_steps(self.num_leaves, &self.indices);
+        fold_multiproof(&steps, &leaf_hashes, &self.siblings, |left, right| {
+            merkle_node_hash_native(&left, &right, options)
+        })
+    }
+}
+
+/// Recomputes the root of a tree of `num_leaves` leaves from the hashes of
+/// the leaves at `indices` and the siblings of a `MerkleMultiProof`. The
+/// sibling words must be range checked.
+///
+/// `num_leaves` and `indices` are not witnesses: they decide which nodes get
+/// hashed and in what order, so they are baked into the circuit, and the
+/// proof shows that the leaves sit at exactly these positions. Every subset
+/// of leaves (and every tree size) needs its own circuit; for a fixed number
+/// of leaves at private positions, use one `merkle_root` per leaf instead.
+pub fn merkle_multiproof_root<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    num_leaves: usize,
+    indices: &[usize],
+    leaf_hashes: &[[U32Target; 8]],
+    siblings: &[[U32Target; 8]],
+    options: MerkleHashOptions,
+) -> [U32Target; 8] {
+    assert_eq!(leaf_hashes.len(), indices.len());
+    assert_split_tree(options);
+    let steps = multiproof_steps(num_leaves, indices);
+    fold_multiproof(&steps, leaf_hashes, siblings, |left, right| {
+        merkle_node_hash(builder, left, right, options)
+    })
+}
+
+pub struct MerkleMultiProofTargets {
+    /// The tree size and leaf positions the circuit was built for.
+    pub num_leaves: usize,
+    pub indices: Vec<usize>,
+    /// Data bits of the proven leaves, in the order of their indices.
+    pub leaves: Vec<Vec<BoolTarget>>,
+    pub siblings: Vec<[U32Target; 8]>,
+    /// The root as eight big-endian words.
+    pub root: [U32Target; 8],
+}
+
+/// Builds a circuit recomputing the root of a tree of `num_leaves` leaves
+/// from the leaves at `indices`, of `leaf_lens_in_bytes` bytes each, and a
+/// multiproof.
+///
+/// The tree size and the leaf positions are part of the circuit, not of the
+/// witness, as in `merkle_multiproof_root`: a proof for another subset of
+/// leaves needs another circuit.
+pub fn make_merkle_multiproof_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    leaf_lens_in_bytes: &[usize],
+    num_leaves: usize,
+    indices: &[usize],
+    options: MerkleHashOptions,
+) -> MerkleMultiProofTargets {
+    assert_eq!(leaf_lens_in_bytes.len(), indices.len());
+    let leaves: Vec<_> = leaf_lens_in_bytes
+        .iter()
+        .map(|&len| add_virtual_bytes(builder, len))
+        .collect();
+    let num_siblings = merkle_multiproof_num_siblings(num_leaves, indices);
+    let siblings = add_virtual_digests(builder, num_siblings);
+    let leaf_hashes: Vec<_> = leaves
+        .iter()
+        .map(|leaf| merkle_leaf_hash(builder, leaf, options))
+        .collect();
+    let root = merkle_multiproof_root(
+        builder,
+        num_leaves,
+        indices,
+        &leaf_hashes,
+        &siblings,
+        options,
+    );
+
+    MerkleMultiProofTargets {
+        num_leaves,
+        indices: indices.to_vec(),
+        leaves,
+        siblings,
+        root,
+    }
+}
+
+pub fn fill_merkle_multiproof_circuits<F: RichField, L: AsRef<[u8]>>(
+    pw: &mut PartialWitness<F>,
+    leaves: &[L],
+    proof: &MerkleMultiProof,
+    targets: &MerkleMultiProofTargets,
+) -> Result<()> {
+    assert_eq!(leaves.len(), targets.leaves.len());
+    assert!(
+        proof.num_leaves == targets.num_leaves && proof.indices == targets.indices,
+        "the circuit proves leaves {:?} of {}, not {:?} of {}",
+        targets.indices,
+        targets.num_leaves,
+        proof.indices,
+        proof.num_leaves
+    );
+    assert_eq!(
+        proof.siblings.len(),
+        targets.siblings.len(),
+        "multiproof must have {} siblings (got {})",
+        targets.siblings.len(),
+        proof.siblings.len()
+    );
+    for (leaf, leaf_targets) in leaves.iter().zip(&targets.leaves) {
+        fill_bytes(pw, leaf.as_ref(), leaf_targets)?;
+    }
+    fill_digests(pw, &proof.siblings, &targets.siblings)
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+
+    use crate::circuit::digest_from_proof;
+    use crate::merkle::{
+        add_virtual_digests, fill_digests, fill_merkle_multiproof_circuits,
+        fill_merkle_proof_circuits, make_merkle_multiproof_circuits, make_merkle_proof_circuits,
+        merkle_node_hash_native, merkle_root_from_leaf_hash, merkle_root_from_leaf_hashes_native,
+        merkle_root_native, MerkleHashOptions, MerkleMultiProof, MerkleProof,
+    };
+    use crate::test_util::hex;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// The leaves of the certificate transparency reference tests.
+    fn rfc6962_leaves() -> Vec<Vec<u8>> {
+        [
+            "",
+            "00",
+            "10",
+            "2021",
+            "3031",
+            "40414243",
+            "5051525354555657",
+            "606162636465666768696a6b6c6d6e6f",
+        ]
+        .into_iter()
+        .map(hex)
+        .collect()
+    }
+
+    #[test]
+    fn test_merkle_root_native() {
+        // Roots of the first 1 to 8 leaves.
+        let roots = [
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+            "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+            "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+        ];
+        let leaves = rfc6962_leaves();
+        for (n, root) in (1..=leaves.len()).zip(roots) {
+            let tree = &leaves[..n];
+            let options = MerkleHashOptions::RFC6962;
+            assert_eq!(merkle_root_native(tree, options).to_vec(), hex(root));
+            for (index, leaf) in tree.iter().enumerate() {
+                let proof = MerkleProof::new(tree, index, options);
+                assert_eq!(proof.root(leaf, options).to_vec(), hex(root));
+            }
+        }
+    }
+
+    #[test]
+    fn test_merkle_proof_circuit() -> anyhow::Result<()> {
+        let rfc6962_leaves = rfc6962_leaves();
+        let unprefixed_leaves: Vec<_> = (0..8u8).map(|i| vec![i; 32]).collect();
+        // Leaf 6 of 7 has a path of two levels and leaf 0 one of three.
+        for (leaves, index, options) in [
+            (&rfc6962_leaves[..7], 6, MerkleHashOptions::RFC6962),
+            (&rfc6962_leaves[..7], 0, MerkleHashOptions::RFC6962),
+            (&unprefixed_leaves[..], 5, MerkleHashOptions::UNPREFIXED),
+        ] {
+            let leaf = &leaves[index];
+            let proof = MerkleProof::new(leaves, index, options);
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let targets =
+                make_merkle_proof_circuits(&mut builder, leaf.len(), proof.siblings.len(), options);
+            for word in &targets.root {
+                builder.register_public_input(word.0);
+            }
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            fill_merkle_proof_circuits(&mut pw, leaf, &proof, &targets)?;
+            let proof = data.prove(pw)?;
+            assert_eq!(
+                digest_from_proof(&proof),
+                merkle_root_native(leaves, options)
+            );
+            data.verify(proof)?;
+        }
+        Ok(())
+    }
+
+    /// Txids of Bitcoin block 100000 as displayed (byte-reversed), and its
+    /// merkle root.
+    const BLOCK_100000_TXIDS: [&str; 4] = [
+        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ];
+    const BLOCK_100000_MERKLE_ROOT: &str =
+        "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766";
+
+    /// Converts between displayed and internal byte order.
+    fn reversed(hash: &str) -> [u8; 32] {
+        let mut bytes: [u8; 32] = hex(hash).try_into().unwrap();
+        bytes.reverse();
+        bytes
+    }
+
+    #[test]
+    fn test_bitcoin_merkle_root_native() {
+        let options = MerkleHashOptions::BITCOIN;
+        let txids = BLOCK_100000_TXIDS.map(reversed);
+        let root = reversed(BLOCK_100000_MERKLE_ROOT);
+        assert_eq!(merkle_root_from_leaf_hashes_native(&txids, options), root);
+        for (index, txid) in txids.iter().enumerate() {
+            let proof = MerkleProof::from_leaf_hashes(&txids, index, options);
+            assert_eq!(proof.root_from_leaf_hash(txid, options), root);
+        }
+
+        // Odd levels pair their last node with itself.
+        let node = |left, right| merkle_node_hash_native(left, right, options);
+        let leaves: Vec<_> = (0..5u8).map(|i| [i; 32]).collect();
+        let n01 = node(&leaves[0], &leaves[1]);
+        let n23 = node(&leaves[2], &leaves[3]);
+        let n44 = node(&leaves[4], &leaves[4]);
+        let expected = node(&node(&n01, &n23), &node(&n44, &n44));
+        assert_eq!(
+            merkle_root_from_leaf_hashes_native(&leaves, options),
+            expected
+        );
+        for index in 0..leaves.len() {
+            let proof = MerkleProof::from_leaf_hashes(&leaves, index, options);
+            assert_eq!(proof.siblings.len(), 3);
+            assert_eq!(proof.root_from_leaf_hash(&leaves[index], options), expected);
+        }
+    }
+
+    #[test]
+    fn test_bitcoin_merkle_proof_circuit() -> anyhow::Result<()> {
+        let options = MerkleHashOptions::BITCOIN;
+        let txids = BLOCK_100000_TXIDS.map(reversed);
+        let proof = MerkleProof::from_leaf_hashes(&txids, 2, options);
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let leaf_hash = add_virtual_digests(&mut builder, 1)[0];
+        let siblings = add_virtual_digests(&mut builder, proof.siblings.len());
+        let directions: Vec<_> = proof
+            .directions
+            .iter()
+            .map(|_| builder.add_virtual_bool_target_safe())
+            .collect();
+        let root =
+            merkle_root_from_leaf_hash(&mut builder, leaf_hash, &siblings, &directions, options);
+        for word in &root {
+            builder.register_public_input(word.0);
+        }
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        fill_digests(&mut pw, &[txids[2]], &[leaf_hash])?;
+        fill_digests(&mut pw, &proof.siblings, &siblings)?;
+        for (&target, &is_right) in directions.iter().zip(&proof.directions) {
+            pw.set_bool_target(target, is_right)?;
+        }
+        let proof = data.prove(pw)?;
+        assert_eq!(
+            digest_from_proof(&proof),
+            reversed(BLOCK_100000_MERKLE_ROOT)
+        );
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_merkle_multiproof_native() {
+        let leaves = rfc6962_leaves();
+        let options = MerkleHashOptions::RFC6962;
+        for n in 1..=leaves.len() {
+            let tree = &leaves[..n];
+            let root = merkle_root_native(tree, options);
+            // Every nonempty subset of the leaves.
+            for subset in 1..1usize << n {
+                let indices: Vec<_> = (0..n).filter(|i| subset >> i & 1 == 1).collect();
+                let proven: Vec<_> = indices.iter().map(|&i| &tree[i]).collect();
+                let proof = MerkleMultiProof::new(tree, &indices, options);
+                assert_eq!(proof.root(&proven, options), root);
+            }
+        }
+
+        // Leaves 2 and 3 are each other's siblings, so together they need two
+        // siblings rather than three each.
+        let proof = MerkleMultiProof::new(&leaves, &[2, 3], options);
+        assert_eq!(proof.siblings.len(), 2);
+        let proof = MerkleMultiProof::new(&leaves, &(0..8).collect::<Vec<_>>(), options);
+        assert!(proof.siblings.is_empty());
+    }
+
+    #[test]
+    fn test_merkle_multiproof_circuit() -> anyhow::Result<()> {
+        let rfc6962_leaves = rfc6962_leaves();
+        let unprefixed_leaves: Vec<_> = (0..8u8).map(|i| vec![i; 32]).collect();
+        for (leaves, indices, options) in [
+            (
+                &rfc6962_leaves[..7],
+                &[1, 2, 6][..],
+                MerkleHashOptions::RFC6962,
+            ),
+            (
+                &unprefixed_leaves[..],
+                &[0, 3, 4, 5][..],
+                MerkleHashOptions::UNPREFIXED,
+            ),
+        ] {
+            let proven: Vec<_> = indices.iter().map(|&i| &leaves[i]).collect();
+            let leaf_lens: Vec<_> = proven.iter().map(|leaf| leaf.len()).collect();
+
+            let proof = MerkleMultiProof::new(leaves, indices, options);
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let targets = make_merkle_multiproof_circuits(
+                &mut builder,
+                &leaf_lens,
+                leaves.len(),
+                indices,
+                options,
+            );
+            for word in &targets.root {
+                builder.register_public_input(word.0);
+            }
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            fill_merkle_multiproof_circuits(&mut pw, &proven, &proof, &targets)?;
+            let proof = data.prove(pw)?;
+            assert_eq!(
+                digest_from_proof(&proof),
+                merkle_root_native(leaves, options)
+            );
+            data.verify(proof)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,711 @@
+use anyhow::{ensure, Result};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitTarget},
+        config::{AlgebraicHasher, GenericConfig, Hasher},
+        proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
+    },
+    recursion::{
+        cyclic_recursion::check_cyclic_proof_verifier_data, dummy_circuit::cyclic_base_proof,
+    },
+};
+use plonky2_u32::gadgets::arithmetic_u32::{CircuitBuilderU32, U32Target};
+
+use crate::circuit::{
+    add_virtual_bytes, array_to_bits, digest_to_bits, fill_bytes, message_words, u32_to_bits_target,
+};
+use crate::gadgets::XorOps;
+use crate::hmac::{
+    hmac_sha256_key_states, hmac_sha256_native, hmac_sha256_of_digest, hmac_sha256_with_key_states,
+};
+use crate::recursion::{cyclic_common_data, pad_to_common_degree, public_input_u32};
+
+/// XOR of `digests`, word by word. Words are split into bits once and folded
+/// two at a time with `Xor3Gate`.
+fn xor_digests<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    digests: &[[U32Target; 8]],
+) -> [U32Target; 8] {
+    if let [digest] = digests {
+        return *digest;
+    }
+    let zero = builder.constant_bool(false);
+    core::array::from_fn(|i| {
+        let words: Vec<_> = digests
+            .iter()
+            .map(|digest| u32_to_bits_target::<F, D, 2>(builder, &digest[i]))
+            .collect();
+        let mut acc = words[0].clone();
+        for pair in words[1..].chunks(2) {
+            acc = (0..32)
+                .map(|bit| {
+                    let c = pair.get(1).map_or(zero, |word| word[bit]);
+                    builder.add_xor3(acc[bit], pair[0][bit], c)
+                })
+                .collect();
+        }
+        U32Target(builder.le_sum_optimized(acc.iter().rev()))
+    })
+}
+
+/// The message of `U_1` for block `block_index`: `salt || INT(block_index)`.
+fn first_block_message<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    salt: &[BoolTarget],
+    block_index: &U32Target,
+) -> Vec<BoolTarget> {
+    let mut msg = salt.to_vec();
+    msg.extend(u32_to_bits_target::<F, D, 2>(builder, block_index));
+    msg
+}
+
+/// PBKDF2-HMAC-SHA256 (RFC 8018) with `iterations` unrolled, returning
+/// `dk_len_in_bytes` bytes of derived key as big-endian bits. The password's
+/// key states are derived once, so each iteration after the first costs two
+/// compressions. Only practical for small iteration counts; see
+/// `Pbkdf2ChunkCircuit` for large ones.
+pub fn pbkdf2_hmac_sha256<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    password: &[BoolTarget],
+    salt: &[BoolTarget],
+    iterations: u32,
+    dk_len_in_bytes: usize,
+) -> Vec<BoolTarget> {
+    assert!(iterations > 0, "PBKDF2 needs at least one iteration");
+    let key = hmac_sha256_key_states(builder, password);
+
+    let mut dk = Vec::with_capacity(dk_len_in_bytes * 8);
+    for block_index in 1..=dk_len_in_bytes.div_ceil(32) {
+        let block_index = builder.constant_u32(block_index as u32);
+        let msg = first_block_message(builder, salt, &block_index);
+        let mut u = hmac_sha256_with_key_states(builder, &key, &msg);
+        let mut us = vec![u];
+        for _ in 1..iterations {
+            u = hmac_sha256_of_digest(builder, &key, u);
+            us.push(u);
+        }
+        let t = xor_digests(builder, &us);
+        dk.extend(digest_to_bits(builder, &t));
+    }
+    dk.truncate(dk_len_in_bytes * 8);
+    dk
+}
+
+/// Native PBKDF2-HMAC-SHA256 (RFC 8018).
+pub fn pbkdf2_hmac_sha256_native(
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    dk_len_in_bytes: usize,
+) -> Vec<u8> {
+    assert!(iterations > 0, "PBKDF2 needs at least one iteration");
+    let mut dk = Vec::with_capacity(dk_len_in_bytes);
+    for block_index in 1..=dk_len_in_bytes.div_ceil(32) as u32 {
+        let mut u = hmac_sha256_native(password, &[salt, &block_index.to_be_bytes()].concat());
+        let mut t = u;
+        for _ in 1..iterations {
+            u = hmac_sha256_native(password, &u);
+            t.iter_mut().zip(&u).for_each(|(t, u)| *t ^= u);
+        }
+        dk.extend_from_slice(&t);
+    }
+    dk.truncate(dk_len_in_bytes);
+    dk
+}
+
+pub struct Pbkdf2HmacSha256Targets {
+    /// Password bits. They are private witnesses.
+    pub password: Vec<BoolTarget>,
+    pub salt: Vec<BoolTarget>,
+    /// The derived key.
+    pub dk: Vec<BoolTarget>,
+}
+
+/// Builds a PBKDF2-HMAC-SHA256 circuit for fixed password and salt lengths
+/// and iteration count, deriving `dk_len_in_bytes` bytes.
+pub fn make_pbkdf2_hmac_sha256_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    password_len_in_bytes: usize,
+    salt_len_in_bytes: usize,
+    iterations: u32,
+    dk_len_in_bytes: usize,
+) -> Pbkdf2HmacSha256Targets {
+    let password = add_virtual_bytes(builder, password_len_in_bytes);
+    let salt = add_virtual_bytes(builder, salt_len_in_bytes);
+    let dk = pbkdf2_hmac_sha256(builder, &password, &salt, iterations, dk_len_in_bytes);
+
+    Pbkdf2HmacSha256Targets { password, salt, dk }
+}
+
+pub fn fill_pbkdf2_hmac_sha256_circuits<F: RichField>(
+    pw: &mut PartialWitness<F>,
+    password: &[u8],
+    salt: &[u8],
+    targets: &Pbkdf2HmacSha256Targets,
+) -> Result<()> {
+    fill_bytes(pw, password, &targets.password)?;
+    fill_bytes(pw, salt, &targets.salt)
+}
+
+// Public inputs of a chunk proof. The cyclic verifier data follows them.
+/// XOR of the `U_j` so far, i.e. the derived key block once done.
+pub const DERIVED_BLOCK_PUBLIC_INPUTS: Range<usize> = 0..8;
+/// The last `U_j`, which the next chunk continues from.
+pub const U_PUBLIC_INPUTS: Range<usize> = 8..16;
+/// Number of iterations done so far.
+pub const ITERATIONS_PUBLIC_INPUT: usize = 16;
+/// Iteration count, the same for every chunk of a chain.
+pub const TARGET_ITERATIONS_PUBLIC_INPUT: usize = 17;
+/// Index of the derived key block, from 1, the same for every chunk of a
+/// chain.
+pub const BLOCK_INDEX_PUBLIC_INPUT: usize = 18;
+/// Poseidon hash of the salt words.
+pub const SALT_COMMITMENT_PUBLIC_INPUTS: Range<usize> = 19..23;
+/// Poseidon hash of the password's HMAC key states and a private random
+/// blinding, which ties every chunk to the password of the first one. Without
+/// the blinding, each password guess could be tested against it with two
+/// compressions, skipping the iterations.
+pub const KEY_COMMITMENT_PUBLIC_INPUTS: Range<usize> = 23..27;
+pub const NUM_CHUNK_PUBLIC_INPUTS: usize = 27;
+
+/// Public inputs of a PBKDF2 chunk proof.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Pbkdf2ChainPublicInputs<F: RichField> {
+    pub derived_block: [u32; 8],
+    pub u: [u32; 8],
+    pub iterations: u32,
+    pub target_iterations: u32,
+    pub block_index: u32,
+    pub salt_commitment: HashOut<F>,
+    pub key_commitment: HashOut<F>,
+}
+
+impl<F: RichField> Pbkdf2ChainPublicInputs<F> {
+    /// Fails if a word or counter does not fit in 32 bits.
+    pub fn from_public_inputs(public_inputs: &[F]) -> Result<Self> {
+        let to_u32 = |i: usize| public_input_u32(public_inputs, i);
+        let to_words = |range: Range<usize>| -> Result<[u32; 8]> {
+            let words = range.map(to_u32).collect::<Result<Vec<_>>>()?;
+            Ok(words.try_into().unwrap())
+        };
+        let to_hash = |range: Range<usize>| HashOut {
+            elements: core::array::from_fn(|i| public_inputs[range.start + i]),
+        };
+        Ok(Self {
+            derived_block: to_words(DERIVED_BLOCK_PUBLIC_INPUTS)?,
+            u: to_words(U_PUBLIC_INPUTS)?,
+            iterations: to_u32(ITERATIONS_PUBLIC_INPUT)?,
+            target_iterations: to_u32(TARGET_ITERATIONS_PUBLIC_INPUT)?,
+            block_index: to_u32(BLOCK_INDEX_PUBLIC_INPUT)?,
+            salt_commitment: to_hash(SALT_COMMITMENT_PUBLIC_INPUTS),
+            key_commitment: to_hash(KEY_COMMITMENT_PUBLIC_INPUTS),
+        })
+    }
+}
+
+struct ChunkTargets<const D: usize> {
+    condition: BoolTarget,
+    inner_proof: ProofWithPublicInputsTarget<D>,
+    verifier_data: VerifierCircuitTarget,
+    password: Vec<BoolTarget>,
+    salt: Vec<BoolTarget>,
+    blinding: HashOutTarget,
+}
+
+/// Adds a chunk of `iterations_per_chunk` iterations continuing from the proof
+/// in `inner_proof` (or from `U_1` when `condition` is false), without the
+/// verification of that proof. Sets `common.num_public_inputs`.
+fn add_chunk_targets<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    password_len_in_bytes: usize,
+    salt_len_in_bytes: usize,
+    iterations_per_chunk: usize,
+    common: &mut CommonCircuitData<F, D>,
+) -> ChunkTargets<D> {
+    let outputs: Vec<Target> = (0..NUM_CHUNK_PUBLIC_INPUTS)
+        .map(|_| builder.add_virtual_public_input())
+        .collect();
+    let verifier_data = builder.add_verifier_data_public_inputs();
+    common.num_public_inputs = builder.num_public_inputs();
+
+    let condition = builder.add_virtual_bool_target_safe();
+    let inner_proof = builder.add_virtual_proof_with_pis(common);
+    let inner = inner_proof.public_inputs.clone();
+
+    // The iteration count and block index are shared by the whole chain; the
+    // base proof carries them too, so they are range checked here.
+    let target_iterations = outputs[TARGET_ITERATIONS_PUBLIC_INPUT];
+    builder.connect(target_iterations, inner[TARGET_ITERATIONS_PUBLIC_INPUT]);
+    let block_index = outputs[BLOCK_INDEX_PUBLIC_INPUT];
+    builder.connect(block_index, inner[BLOCK_INDEX_PUBLIC_INPUT]);
+    builder.range_check_u32(vec![U32Target(target_iterations), U32Target(block_index)]);
+
+    // Every chunk derives the key states from its password, which has to be
+    // the one of the previous chunk as far as the commitment can tell. The
+    // blinding has to be the same throughout the chain too.
+    let password = add_virtual_bytes(builder, password_len_in_bytes);
+    let key = hmac_sha256_key_states(builder, &password);
+    let blinding = builder.add_virtual_hash();
+    let key_words = key
+        .inner
+        .iter()
+        .chain(&key.outer)
+        .map(|w| w.0)
+        .chain(blinding.elements)
+        .collect();
+    let key_commitment = builder.hash_n_to_hash_no_pad::<PoseidonHash>(key_words);
+    for (i, &element) in key_commitment.elements.iter().enumerate() {
+        let expected = builder.select(
+            condition,
+            inner[KEY_COMMITMENT_PUBLIC_INPUTS.start + i],
+            element,
+        );
+        builder.connect(element, expected);
+        builder.connect(outputs[KEY_COMMITMENT_PUBLIC_INPUTS.start + i], element);
+    }
+
+    // The salt only matters to the first chunk; later ones pass on its
+    // commitment.
+    let salt = add_virtual_bytes(builder, salt_len_in_bytes);
+    let salt_words = message_words(builder, &salt).iter().map(|w| w.0).collect();
+    let salt_commitment = builder.hash_n_to_hash_no_pad::<PoseidonHash>(salt_words);
+    for (i, &element) in salt_commitment.elements.iter().enumerate() {
+        let commitment = builder.select(
+            condition,
+            inner[SALT_COMMITMENT_PUBLIC_INPUTS.start + i],
+            element,
+        );
+        builder.connect(outputs[SALT_COMMITMENT_PUBLIC_INPUTS.start + i], commitment);
+    }
+    let msg = first_block_message(builder, &salt, &U32Target(block_index));
+    let u1 = hmac_sha256_with_key_states(builder, &key, &msg);
+
+    // Continue from the previous chunk, or start from `U_1`.
+    let mut u: [U32Target; 8] = core::array::from_fn(|i| {
+        U32Target(builder.select(condition, inner[U_PUBLIC_INPUTS.start + i], u1[i].0))
+    });
+    let derived_block: [U32Target; 8] = core::array::from_fn(|i| {
+        let prev = inner[DERIVED_BLOCK_PUBLIC_INPUTS.start + i];
+        U32Target(builder.select(condition, prev, u1[i].0))
+    });
+    let one = builder.one();
+    let mut iterations = builder.select(condition, inner[ITERATIONS_PUBLIC_INPUT], one);
+
+    let mut terms = vec![derived_block];
+    for _ in 0..iterations_per_chunk {
+        // Iterations past the count leave the state unchanged and add zero.
+        let finished = builder.is_equal(iterations, target_iterations);
+        let active = builder.not(finished);
+        let next = hmac_sha256_of_digest(builder, &key, u);
+        u = core::array::from_fn(|i| U32Target(builder.select(active, next[i].0, u[i].0)));
+        terms.push(next.map(|w| U32Target(builder.mul(active.target, w.0))));
+        iterations = builder.add(iterations, active.target);
+    }
+    let derived_block = xor_digests(builder, &terms);
+
+    for i in 0..8 {
+        builder.connect(
+            outputs[DERIVED_BLOCK_PUBLIC_INPUTS.start + i],
+            derived_block[i].0,
+        );
+        builder.connect(outputs[U_PUBLIC_INPUTS.start + i], u[i].0);
+    }
+    builder.connect(outputs[ITERATIONS_PUBLIC_INPUT], iterations);
+
+    ChunkTargets {
+        condition,
+        inner_proof,
+        verifier_data,
+        password,
+        salt,
+        blinding,
+    }
+}
+
+/// Proves knowledge of a password whose PBKDF2-HMAC-SHA256 derived key block
+/// is `derived_block`, for iteration counts too large to unroll. Each chunk
+/// proof runs `iterations_per_chunk` iterations and recursively verifies the
+/// previous one; the first chunk also computes `U_1` from the salt. Longer
+/// keys take one chain per 32-byte block.
+///
+/// Only the proof of the last chunk should be published: an earlier one
+/// exposes `U_j` after fewer iterations than the full count.
+pub struct Pbkdf2ChunkCircuit<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub data: CircuitData<F, C, D>,
+    iterations_per_chunk: usize,
+    targets: ChunkTargets<D>,
+}
+
+impl<F, C, const D: usize> Pbkdf2ChunkCircuit<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub fn new(
+        config: CircuitConfig,
+        password_len_in_bytes: usize,
+        salt_len_in_bytes: usize,
+        iterations_per_chunk: usize,
+    ) -> Result<Self> {
+        let add_chunk = |builder: &mut CircuitBuilder<F, D>,
+                         common: &mut CommonCircuitData<F, D>| {
+            add_chunk_targets(
+                builder,
+                password_len_in_bytes,
+                salt_len_in_bytes,
+                iterations_per_chunk,
+                common,
+            )
+        };
+        let mut common = cyclic_common_data::<F, C, D>(&config, |builder, common| {
+            let targets = add_chunk(builder, common);
+            (
+                targets.condition,
+                targets.inner_proof,
+                targets.verifier_data,
+            )
+        });
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = add_chunk(&mut builder, &mut common);
+        builder.conditionally_verify_cyclic_proof_or_dummy::<C>(
+            targets.condition,
+            &targets.inner_proof,
+            &common,
+        )?;
+        pad_to_common_degree(&mut builder, &common);
+        let data = builder.build::<C>();
+        ensure!(
+            data.common == common,
+            "chunk circuit does not fit the common data found for it; raise COMMON_DATA_HEADROOM"
+        );
+
+        Ok(Self {
+            data,
+            iterations_per_chunk,
+            targets,
+        })
+    }
+
+    /// Number of chunks for `iterations` iterations; `U_1` comes for free with
+    /// the first chunk.
+    pub fn num_chunks(&self, iterations: u32) -> usize {
+        (iterations.saturating_sub(1) as usize)
+            .div_ceil(self.iterations_per_chunk)
+            .max(1)
+    }
+
+    /// A fresh random blinding for the key commitment of a chain.
+    pub fn random_blinding() -> HashOut<F> {
+        HashOut {
+            elements: core::array::from_fn(|_| F::rand()),
+        }
+    }
+
+    /// Proves chunk `chunk` of the derivation of block `block_index` on top of
+    /// `prev`, the proof of the previous chunk (`None` for the first one).
+    /// `blinding` must be the same for every chunk of a chain; see
+    /// `random_blinding`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prove_chunk(
+        &self,
+        password: &[u8],
+        salt: &[u8],
+        block_index: u32,
+        iterations: u32,
+        blinding: HashOut<F>,
+        chunk: usize,
+        prev: Option<&ProofWithPublicInputs<F, C, D>>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(iterations > 0, "PBKDF2 needs at least one iteration");
+        ensure!(
+            chunk < self.num_chunks(iterations),
+            "chunk {} out of range",
+            chunk
+        );
+        ensure!(
+            (chunk == 0) == prev.is_none(),
+            "exactly the first chunk starts without a previous proof"
+        );
+
+        let mut pw = PartialWitness::new();
+        fill_bytes(&mut pw, password, &self.targets.password)?;
+        fill_bytes(&mut pw, salt, &self.targets.salt)?;
+        pw.set_hash_target(self.targets.blinding, blinding)?;
+
+        match prev {
+            Some(prev) => {
+                pw.set_bool_target(self.targets.condition, true)?;
+                pw.set_proof_with_pis_target(&self.targets.inner_proof, prev)?;
+            }
+            None => {
+                pw.set_bool_target(self.targets.condition, false)?;
+                let base_proof = cyclic_base_proof(
+                    &self.data.common,
+                    &self.data.verifier_only,
+                    HashMap::from([
+                        (
+                            TARGET_ITERATIONS_PUBLIC_INPUT,
+                            F::from_canonical_u32(iterations),
+                        ),
+                        (BLOCK_INDEX_PUBLIC_INPUT, F::from_canonical_u32(block_index)),
+                    ]),
+                );
+                pw.set_proof_with_pis_target(&self.targets.inner_proof, &base_proof)?;
+            }
+        }
+        pw.set_verifier_data_target(&self.targets.verifier_data, &self.data.verifier_only)?;
+
+        self.data.prove(pw)
+    }
+
+    /// Proves every chunk under a fresh blinding, returning the proof of the
+    /// last one.
+    pub fn prove(
+        &self,
+        password: &[u8],
+        salt: &[u8],
+        block_index: u32,
+        iterations: u32,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let blinding = Self::random_blinding();
+        let mut proof =
+            self.prove_chunk(password, salt, block_index, iterations, blinding, 0, None)?;
+        for chunk in 1..self.num_chunks(iterations) {
+            proof = self.prove_chunk(
+                password,
+                salt,
+                block_index,
+                iterations,
+                blinding,
+                chunk,
+                Some(&proof),
+            )?;
+        }
+        Ok(proof)
+    }
+
+    /// Verifies the proof of the last chunk, returning its public inputs. The
+    /// derived block is then block `block_index` of the derived key.
+    pub fn verify(
+        &self,
+        proof: ProofWithPublicInputs<F, C, D>,
+    ) -> Result<Pbkdf2ChainPublicInputs<F>> {
+        check_cyclic_proof_verifier_data(&proof, &self.data.verifier_only, &self.data.common)?;
+        let public_inputs = Pbkdf2ChainPublicInputs::from_public_inputs(&proof.public_inputs)?;
+        self.data.verify(proof)?;
+        ensure!(
+            public_inputs.iterations == public_inputs.target_iterations,
+            "proof covers {} of {} iterations",
+            public_inputs.iterations,
+            public_inputs.target_iterations
+        );
+        Ok(public_inputs)
+    }
+
+    /// Native counterpart of the salt commitment in the public inputs.
+    pub fn salt_commitment(salt: &[u8]) -> HashOut<F> {
+        let words: Vec<F> = array_to_bits(salt)
+            .chunks(32)
+            .map(|word| {
+                let word = word.iter().fold(0u32, |acc, &bit| (acc << 1) | bit as u32);
+                F::from_canonical_u32(word)
+            })
+            .collect();
+        PoseidonHash::hash_no_pad(&words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        field::types::Field,
+        iop::witness::PartialWitness,
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+
+    use crate::circuit::array_to_bits;
+    use crate::pbkdf2::{
+        fill_pbkdf2_hmac_sha256_circuits, make_pbkdf2_hmac_sha256_circuits,
+        pbkdf2_hmac_sha256_native, Pbkdf2ChainPublicInputs, Pbkdf2ChunkCircuit,
+        NUM_CHUNK_PUBLIC_INPUTS, TARGET_ITERATIONS_PUBLIC_INPUT,
+    };
+    use crate::test_util::hex;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    struct Vector {
+        password: &'static [u8],
+        salt: &'static [u8],
+        iterations: u32,
+        dk: Vec<u8>,
+    }
+
+    /// The 64-byte derived keys are the RFC 7914 test vectors.
+    fn vectors() -> Vec<Vector> {
+        [
+            (
+                &b"password"[..],
+                &b"salt"[..],
+                1,
+                "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b",
+            ),
+            (
+                b"password",
+                b"salt",
+                2,
+                "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43",
+            ),
+            (
+                b"password",
+                b"salt",
+                4096,
+                "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a",
+            ),
+            (
+                b"passwordPASSWORDpassword",
+                b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                "348c89dbcbd32b2f32d814b8116e84cf2b17347ebc1800181c4e2a1fb8dd53e1c635518c7dac47e9",
+            ),
+            (
+                b"passwd",
+                b"salt",
+                1,
+                "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+                 49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783",
+            ),
+            (
+                b"Password",
+                b"NaCl",
+                80000,
+                "4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56\
+                 a1d425a1225833549adb841b51c9b3176a272bdebba1d078478f62b397f33c8d",
+            ),
+        ]
+        .into_iter()
+        .map(|(password, salt, iterations, dk)| Vector {
+            password,
+            salt,
+            iterations,
+            dk: hex(dk),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_pbkdf2_hmac_sha256_native() {
+        for v in vectors() {
+            assert_eq!(
+                pbkdf2_hmac_sha256_native(v.password, v.salt, v.iterations, v.dk.len()),
+                v.dk
+            );
+        }
+    }
+
+    #[test]
+    fn test_pbkdf2_hmac_sha256_circuit() -> anyhow::Result<()> {
+        // Two iterations, and a single one over two blocks.
+        let vectors = vectors();
+        for v in [&vectors[1], &vectors[4]] {
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let targets = make_pbkdf2_hmac_sha256_circuits(
+                &mut builder,
+                v.password.len(),
+                v.salt.len(),
+                v.iterations,
+                v.dk.len(),
+            );
+            for bit in &targets.dk {
+                builder.register_public_input(bit.target);
+            }
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            fill_pbkdf2_hmac_sha256_circuits(&mut pw, v.password, v.salt, &targets)?;
+            let proof = data.prove(pw)?;
+            let expected: Vec<_> = array_to_bits(&v.dk).into_iter().map(F::from_bool).collect();
+            assert_eq!(proof.public_inputs, expected);
+            data.verify(proof)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pbkdf2_chunk_circuit() -> anyhow::Result<()> {
+        let (password, salt) = (b"password", b"salt");
+        let circuit = Pbkdf2ChunkCircuit::<F, C, D>::new(
+            CircuitConfig::standard_recursion_config(),
+            password.len(),
+            salt.len(),
+            2,
+        )?;
+
+        // Four iterations: `U_1` and two more in the first chunk, then one and
+        // an idle one in the second.
+        let iterations = 4;
+        assert_eq!(circuit.num_chunks(iterations), 2);
+        let blinding = Pbkdf2ChunkCircuit::<F, C, D>::random_blinding();
+        let first = circuit.prove_chunk(password, salt, 1, iterations, blinding, 0, None)?;
+        assert!(circuit.verify(first.clone()).is_err());
+        let proof =
+            circuit.prove_chunk(password, salt, 1, iterations, blinding, 1, Some(&first))?;
+        let public_inputs = circuit.verify(proof)?;
+        assert_eq!(
+            public_inputs.key_commitment,
+            Pbkdf2ChainPublicInputs::from_public_inputs(&first.public_inputs)?.key_commitment
+        );
+
+        // Under another blinding, the same password commits differently.
+        let other_blinding = Pbkdf2ChunkCircuit::<F, C, D>::random_blinding();
+        let other = circuit.prove_chunk(password, salt, 1, iterations, other_blinding, 0, None)?;
+        assert_ne!(
+            Pbkdf2ChainPublicInputs::from_public_inputs(&other.public_inputs)?.key_commitment,
+            public_inputs.key_commitment
+        );
+
+        let dk = pbkdf2_hmac_sha256_native(password, salt, iterations, 32);
+        let expected: [u32; 8] =
+            core::array::from_fn(|i| u32::from_be_bytes(dk[4 * i..4 * i + 4].try_into().unwrap()));
+        assert_eq!(public_inputs.derived_block, expected);
+        assert_eq!(public_inputs.block_index, 1);
+        assert_eq!(
+            public_inputs.salt_commitment,
+            Pbkdf2ChunkCircuit::<F, C, D>::salt_commitment(salt)
+        );
+
+        Ok(())
+    }
+    #[test]
+    fn test_chain_public_inputs_reject_wide_values() {
+        let mut public_inputs = vec![F::ZERO; NUM_CHUNK_PUBLIC_INPUTS];
+        assert!(Pbkdf2ChainPublicInputs::from_public_inputs(&public_inputs).is_ok());
+        public_inputs[TARGET_ITERATIONS_PUBLIC_INPUT] = F::from_canonical_u64(1 << 32);
+        assert!(Pbkdf2ChainPublicInputs::from_public_inputs(&public_inputs).is_err());
+    }
+}
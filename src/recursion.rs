@@ -0,0 +1,497 @@
+use anyhow::{ensure, Result};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use plonky2::{
+    field::extension::Extendable,
+    gates::noop::NoopGate,
+    hash::{
+        hash_types::{HashOut, RichField},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitTarget},
+        config::{AlgebraicHasher, GenericConfig, Hasher},
+        proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
+    },
+    recursion::{
+        cyclic_recursion::check_cyclic_proof_verifier_data, dummy_circuit::cyclic_base_proof,
+    },
+};
+use plonky2_u32::gadgets::arithmetic_u32::U32Target;
+
+use crate::circuit::{
+    constrain_padding_block, message_words, sha256_compress, u32_to_bits_target,
+    variable_length_padded_bits, H256,
+};
+use crate::gadgets::XorOps;
+
+// Public inputs of a chunk proof. The cyclic verifier data follows them.
+pub const STATE_PUBLIC_INPUTS: Range<usize> = 0..8;
+/// Message length in bits as two words (high, low), the same for every chunk
+/// of a chain.
+pub const MSG_LEN_PUBLIC_INPUTS: Range<usize> = 8..10;
+/// Number of block slots hashed so far, including the zero slots after the
+/// last block.
+pub const BLOCKS_PUBLIC_INPUT: usize = 10;
+/// Whether the `1` padding bit has been hashed.
+pub const PADDING_PUBLIC_INPUT: usize = 11;
+/// Whether the last block has been hashed, i.e. the state is the digest.
+pub const DONE_PUBLIC_INPUT: usize = 12;
+/// Poseidon commitment chained over the padded message words.
+pub const COMMITMENT_PUBLIC_INPUTS: Range<usize> = 13..17;
+pub const NUM_CHUNK_PUBLIC_INPUTS: usize = 17;
+
+/// Messages must be shorter than this many bits: the high length word may not
+/// be `u32::MAX`, so that the length fits in a Goldilocks element.
+pub const MAX_MSG_LEN_IN_BITS: u64 = (u32::MAX as u64) << 32;
+
+/// No-op rows reserved while searching for the common circuit data. They stand
+/// in for the constant verifier data of the dummy proof in the real circuit.
+const COMMON_DATA_HEADROOM: usize = 256;
+
+/// Public inputs of a chunk proof.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Sha256ChainPublicInputs<F: RichField> {
+    pub state: [u32; 8],
+    /// Message length in bits.
+    pub msg_len: u64,
+    pub blocks: u64,
+    pub padding: bool,
+    pub done: bool,
+    pub message_commitment: HashOut<F>,
+}
+
+/// Reads public input `i` of a chain proof as a 32-bit word.
+pub(crate) fn public_input_u32<F: RichField>(public_inputs: &[F], i: usize) -> Result<u32> {
+    let value = public_inputs[i].to_canonical_u64();
+    ensure!(
+        value <= u32::MAX as u64,
+        "public input {} is not a 32-bit value: {}",
+        i,
+        value
+    );
+    Ok(value as u32)
+}
+
+impl<F: RichField> Sha256ChainPublicInputs<F> {
+    /// Fails if a state or length word does not fit in 32 bits.
+    pub fn from_public_inputs(public_inputs: &[F]) -> Result<Self> {
+        let to_u32 = |i: usize| public_input_u32(public_inputs, i);
+        let state = STATE_PUBLIC_INPUTS
+            .map(to_u32)
+            .collect::<Result<Vec<_>>>()?;
+        let hi = to_u32(MSG_LEN_PUBLIC_INPUTS.start)?;
+        let lo = to_u32(MSG_LEN_PUBLIC_INPUTS.start + 1)?;
+        Ok(Self {
+            state: state.try_into().unwrap(),
+            msg_len: ((hi as u64) << 32) | lo as u64,
+            blocks: public_inputs[BLOCKS_PUBLIC_INPUT].to_canonical_u64(),
+            padding: public_inputs[PADDING_PUBLIC_INPUT].is_one(),
+            done: public_inputs[DONE_PUBLIC_INPUT].is_one(),
+            message_commitment: HashOut {
+                elements: core::array::from_fn(|i| {
+                    public_inputs[COMMITMENT_PUBLIC_INPUTS.start + i]
+                }),
+            },
+        })
+    }
+}
+
+struct ChunkTargets<const D: usize> {
+    condition: BoolTarget,
+    inner_proof: ProofWithPublicInputsTarget<D>,
+    verifier_data: VerifierCircuitTarget,
+    message: Vec<BoolTarget>,
+}
+
+/// Adds a chunk of `blocks_per_chunk` blocks continuing from the proof in
+/// `inner_proof` (or from the IV when `condition` is false), without the
+/// verification of that proof. Sets `common.num_public_inputs`.
+fn add_chunk_targets<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    blocks_per_chunk: usize,
+    common: &mut CommonCircuitData<F, D>,
+) -> ChunkTargets<D> {
+    let outputs: Vec<Target> = (0..NUM_CHUNK_PUBLIC_INPUTS)
+        .map(|_| builder.add_virtual_public_input())
+        .collect();
+    let verifier_data = builder.add_verifier_data_public_inputs();
+    common.num_public_inputs = builder.num_public_inputs();
+
+    let condition = builder.add_virtual_bool_target_safe();
+    let inner_proof = builder.add_virtual_proof_with_pis(common);
+    let inner = inner_proof.public_inputs.clone();
+
+    // The length is shared by the whole chain; the base proof carries it too.
+    // Splitting the words range checks them and gives the 64 length bits of
+    // the padding. A high word below `u32::MAX` keeps `msg_len` exact.
+    for i in MSG_LEN_PUBLIC_INPUTS {
+        builder.connect(outputs[i], inner[i]);
+    }
+    let [hi, lo] = core::array::from_fn(|i| outputs[MSG_LEN_PUBLIC_INPUTS.start + i]);
+    let mut len_bits = u32_to_bits_target::<F, D, 2>(builder, &U32Target(hi));
+    len_bits.extend(u32_to_bits_target::<F, D, 2>(builder, &U32Target(lo)));
+    let hi_max = builder.constant(F::from_canonical_u32(u32::MAX));
+    let hi_is_max = builder.is_equal(hi, hi_max);
+    builder.assert_zero(hi_is_max.target);
+    let msg_len = builder.mul_const_add(F::from_canonical_u64(1 << 32), hi, lo);
+
+    // The last block is `floor((msg_len + 64) / 512)`: `msg_len >> 9`, plus
+    // one when the low 9 bits are at least 448 and the length spills over.
+    let lo_blocks = builder.le_sum_optimized(len_bits[32..55].iter().rev());
+    let len_blocks = builder.mul_const_add(F::from_canonical_u64(1 << 23), hi, lo_blocks);
+    let spills = builder.and(len_bits[55], len_bits[56]);
+    let spills = builder.and(spills, len_bits[57]);
+    let last_block = builder.add(len_blocks, spills.target);
+
+    // Continue from the previous chunk, or start from the IV.
+    let mut state: [U32Target; 8] = core::array::from_fn(|i| {
+        let iv = builder.constant(F::from_canonical_u32(H256[i]));
+        U32Target(builder.select(condition, inner[STATE_PUBLIC_INPUTS.start + i], iv))
+    });
+    let mut blocks = builder.mul(condition.target, inner[BLOCKS_PUBLIC_INPUT]);
+    let mut is_pad = builder.mul(condition.target, inner[PADDING_PUBLIC_INPUT]);
+    let mut done = builder.mul(condition.target, inner[DONE_PUBLIC_INPUT]);
+    let mut commitment_inputs: Vec<Target> = COMMITMENT_PUBLIC_INPUTS
+        .map(|i| builder.mul(condition.target, inner[i]))
+        .collect();
+
+    let mut message = Vec::new();
+    for _ in 0..blocks_per_chunk * 512 {
+        message.push(builder.add_virtual_bool_target_unsafe());
+    }
+    let words = message_words(builder, &message);
+    for (block, block_words) in message.chunks(512).zip(words.chunks(16)) {
+        let is_last = builder.is_equal(blocks, last_block);
+        let block_start = builder.mul_const(F::from_canonical_u64(512), blocks);
+        is_pad = constrain_padding_block(
+            builder,
+            block,
+            block_start,
+            msg_len,
+            &len_bits,
+            is_last,
+            is_pad,
+        );
+
+        // Slots after the last block are all zero (the padding constraints
+        // see to that) and leave the state unchanged.
+        let new_state = sha256_compress(builder, state, block_words.try_into().unwrap());
+        let not_done = builder.not(BoolTarget::new_unsafe(done));
+        for i in 0..8 {
+            state[i] = U32Target(builder.select(not_done, new_state[i].0, state[i].0));
+        }
+        done = builder.add(done, is_last.target);
+        blocks = builder.add_const(blocks, F::ONE);
+    }
+    commitment_inputs.extend(words.iter().map(|w| w.0));
+    let commitment = builder.hash_n_to_hash_no_pad::<PoseidonHash>(commitment_inputs);
+
+    for (i, word) in state.iter().enumerate() {
+        builder.connect(outputs[STATE_PUBLIC_INPUTS.start + i], word.0);
+    }
+    builder.connect(outputs[BLOCKS_PUBLIC_INPUT], blocks);
+    builder.connect(outputs[PADDING_PUBLIC_INPUT], is_pad);
+    builder.connect(outputs[DONE_PUBLIC_INPUT], done);
+    for (i, element) in commitment.elements.iter().enumerate() {
+        builder.connect(outputs[COMMITMENT_PUBLIC_INPUTS.start + i], *element);
+    }
+
+    ChunkTargets {
+        condition,
+        inner_proof,
+        verifier_data,
+        message,
+    }
+}
+
+/// Finds the common circuit data of a cyclic circuit, which has to verify
+/// proofs with that same data. `add_step` adds the circuit's own targets and
+/// returns the condition, proof and verifier data of the cyclic verifier. Each
+/// round builds the step with a verifier for the previous round's data until
+/// the shape stops changing.
+pub(crate) fn cyclic_common_data<F, C, const D: usize>(
+    config: &CircuitConfig,
+    add_step: impl Fn(
+        &mut CircuitBuilder<F, D>,
+        &mut CommonCircuitData<F, D>,
+    ) -> (
+        BoolTarget,
+        ProofWithPublicInputsTarget<D>,
+        VerifierCircuitTarget,
+    ),
+) -> CommonCircuitData<F, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let mut common = CircuitBuilder::<F, D>::new(config.clone())
+        .build::<C>()
+        .common;
+    loop {
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let (condition, inner_proof, verifier_data) = add_step(&mut builder, &mut common);
+        let other_proof = builder.add_virtual_proof_with_pis(&common);
+        let other_verifier_data =
+            builder.add_virtual_verifier_data(common.config.fri_config.cap_height);
+        builder.conditionally_verify_proof::<C>(
+            condition,
+            &inner_proof,
+            &verifier_data,
+            &other_proof,
+            &other_verifier_data,
+            &common,
+        );
+        for _ in 0..COMMON_DATA_HEADROOM {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        let next = builder.build::<C>().common;
+        if next == common {
+            return common;
+        }
+        common = next;
+    }
+}
+
+/// The common data was found with some headroom; pads the real circuit up to
+/// its degree.
+pub(crate) fn pad_to_common_degree<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    common: &CommonCircuitData<F, D>,
+) {
+    let min_gates = (1 << (common.degree_bits() - 1)) + 1;
+    while builder.num_gates() < min_gates {
+        builder.add_gate(NoopGate, vec![]);
+    }
+}
+
+/// Hashes long messages `blocks_per_chunk` blocks at a time. Each chunk proof
+/// recursively verifies the previous one and exposes the chaining state, so
+/// the last proof attests to the digest of the whole message. Messages may be
+/// up to `MAX_MSG_LEN_IN_BITS` long.
+pub struct Sha256ChunkCircuit<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub data: CircuitData<F, C, D>,
+    blocks_per_chunk: usize,
+    targets: ChunkTargets<D>,
+}
+
+impl<F, C, const D: usize> Sha256ChunkCircuit<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub fn new(config: CircuitConfig, blocks_per_chunk: usize) -> Result<Self> {
+        let mut common = cyclic_common_data::<F, C, D>(&config, |builder, common| {
+            let targets = add_chunk_targets(builder, blocks_per_chunk, common);
+            (
+                targets.condition,
+                targets.inner_proof,
+                targets.verifier_data,
+            )
+        });
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let targets = add_chunk_targets(&mut builder, blocks_per_chunk, &mut common);
+        builder.conditionally_verify_cyclic_proof_or_dummy::<C>(
+            targets.condition,
+            &targets.inner_proof,
+            &common,
+        )?;
+        pad_to_common_degree(&mut builder, &common);
+        let data = builder.build::<C>();
+        ensure!(
+            data.common == common,
+            "chunk circuit does not fit the common data found for it; raise COMMON_DATA_HEADROOM"
+        );
+
+        Ok(Self {
+            data,
+            blocks_per_chunk,
+            targets,
+        })
+    }
+
+    pub fn num_chunks(&self, msg_len_bytes: usize) -> usize {
+        let msg_blocks = (msg_len_bytes * 8 + 65).div_ceil(512);
+        msg_blocks.div_ceil(self.blocks_per_chunk)
+    }
+
+    fn padded_message(&self, msg: &[u8]) -> Vec<bool> {
+        let chunk_bits = self.blocks_per_chunk * 512;
+        variable_length_padded_bits(msg, self.num_chunks(msg.len()) * chunk_bits)
+    }
+
+    /// Proves chunk `chunk` of `msg` on top of `prev`, the proof of the
+    /// previous chunk (`None` for the first one).
+    pub fn prove_chunk(
+        &self,
+        msg: &[u8],
+        chunk: usize,
+        prev: Option<&ProofWithPublicInputs<F, C, D>>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(
+            chunk < self.num_chunks(msg.len()),
+            "chunk {} out of range",
+            chunk
+        );
+        ensure!(
+            (chunk == 0) == prev.is_none(),
+            "exactly the first chunk starts without a previous proof"
+        );
+        let msg_len_in_bits = msg.len() as u64 * 8;
+        ensure!(
+            msg_len_in_bits < MAX_MSG_LEN_IN_BITS,
+            "message of {} bits is too long",
+            msg_len_in_bits
+        );
+        let chunk_bits = self.blocks_per_chunk * 512;
+        let padded = self.padded_message(msg);
+
+        let mut pw = PartialWitness::new();
+        let chunk_bits_range = chunk * chunk_bits..(chunk + 1) * chunk_bits;
+        for (target, &bit) in self.targets.message.iter().zip(&padded[chunk_bits_range]) {
+            pw.set_bool_target(*target, bit)?;
+        }
+
+        match prev {
+            Some(prev) => {
+                pw.set_bool_target(self.targets.condition, true)?;
+                pw.set_proof_with_pis_target(&self.targets.inner_proof, prev)?;
+            }
+            None => {
+                pw.set_bool_target(self.targets.condition, false)?;
+                let base_proof = cyclic_base_proof(
+                    &self.data.common,
+                    &self.data.verifier_only,
+                    HashMap::from([
+                        (
+                            MSG_LEN_PUBLIC_INPUTS.start,
+                            F::from_canonical_u64(msg_len_in_bits >> 32),
+                        ),
+                        (
+                            MSG_LEN_PUBLIC_INPUTS.start + 1,
+                            F::from_canonical_u32(msg_len_in_bits as u32),
+                        ),
+                    ]),
+                );
+                pw.set_proof_with_pis_target(&self.targets.inner_proof, &base_proof)?;
+            }
+        }
+        pw.set_verifier_data_target(&self.targets.verifier_data, &self.data.verifier_only)?;
+
+        self.data.prove(pw)
+    }
+
+    /// Proves every chunk of `msg`, returning the proof of the last one.
+    pub fn prove(&self, msg: &[u8]) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let mut proof = self.prove_chunk(msg, 0, None)?;
+        for chunk in 1..self.num_chunks(msg.len()) {
+            proof = self.prove_chunk(msg, chunk, Some(&proof))?;
+        }
+        Ok(proof)
+    }
+
+    /// Verifies the proof of the last chunk, returning its public inputs. The
+    /// state is then the SHA-256 digest of the message.
+    pub fn verify(
+        &self,
+        proof: ProofWithPublicInputs<F, C, D>,
+    ) -> Result<Sha256ChainPublicInputs<F>> {
+        check_cyclic_proof_verifier_data(&proof, &self.data.verifier_only, &self.data.common)?;
+        let public_inputs = Sha256ChainPublicInputs::from_public_inputs(&proof.public_inputs)?;
+        self.data.verify(proof)?;
+        ensure!(
+            public_inputs.done,
+            "proof does not cover the last block of the message"
+        );
+        Ok(public_inputs)
+    }
+
+    /// Native counterpart of the message commitment in the public inputs.
+    pub fn message_commitment(&self, msg: &[u8]) -> HashOut<F> {
+        let mut commitment = HashOut {
+            elements: [F::ZERO; 4],
+        };
+        for chunk in self.padded_message(msg).chunks(self.blocks_per_chunk * 512) {
+            let mut inputs = commitment.elements.to_vec();
+            for word in chunk.chunks(32) {
+                let word = word.iter().fold(0u32, |acc, &bit| (acc << 1) | bit as u32);
+                inputs.push(F::from_canonical_u32(word));
+            }
+            commitment = PoseidonHash::hash_no_pad(&inputs);
+        }
+        commitment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::plonk::{
+        circuit_data::CircuitConfig,
+        config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+    use sha2::Digest;
+
+    use crate::circuit::EXAMPLE_MESSAGE;
+    use crate::recursion::Sha256ChunkCircuit;
+
+    #[test]
+    fn test_sha256_chunk_circuit() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let circuit =
+            Sha256ChunkCircuit::<F, C, D>::new(CircuitConfig::standard_recursion_config(), 2)?;
+
+        // 992 bits: the `1` bit lands in block 1 and the length in block 2, so
+        // the padding spans both chunks and the second chunk has an empty slot.
+        let msg = &EXAMPLE_MESSAGE[..124];
+        assert_eq!(circuit.num_chunks(msg.len()), 2);
+        let digest = sha2::Sha256::digest(msg);
+
+        let proof = circuit.prove(msg)?;
+        let public_inputs = circuit.verify(proof)?;
+
+        let expected: [u32; 8] = core::array::from_fn(|i| {
+            u32::from_be_bytes(digest[4 * i..4 * i + 4].try_into().unwrap())
+        });
+        assert_eq!(public_inputs.state, expected);
+        assert_eq!(public_inputs.msg_len, msg.len() as u64 * 8);
+        assert_eq!(public_inputs.blocks, 4);
+        assert_eq!(
+            public_inputs.message_commitment,
+            circuit.message_commitment(msg)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sha256_chunk_circuit_rejects_unfinished_chain() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let circuit =
+            Sha256ChunkCircuit::<F, C, D>::new(CircuitConfig::standard_recursion_config(), 1)?;
+
+        let msg = &EXAMPLE_MESSAGE[..100];
+        assert_eq!(circuit.num_chunks(msg.len()), 2);
+
+        let proof = circuit.prove_chunk(msg, 0, None)?;
+        assert!(circuit.verify(proof).is_err());
+
+        Ok(())
+    }
+}
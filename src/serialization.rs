@@ -0,0 +1,229 @@
+use std::marker::PhantomData;
+
+use plonky2::{
+    field::extension::Extendable,
+    gadgets::{
+        arithmetic::EqualityGenerator,
+        arithmetic_extension::QuotientGeneratorExtension,
+        range_check::LowHighGenerator,
+        split_base::BaseSumGenerator,
+        split_join::{SplitGenerator, WireSplitGenerator},
+    },
+    gates::{
+        arithmetic_base::{ArithmeticBaseGenerator, ArithmeticGate},
+        arithmetic_extension::{ArithmeticExtensionGate, ArithmeticExtensionGenerator},
+        base_sum::{BaseSplitGenerator, BaseSumGate},
+        constant::ConstantGate,
+        coset_interpolation::{CosetInterpolationGate, InterpolationGenerator},
+        exponentiation::{ExponentiationGate, ExponentiationGenerator},
+        lookup::{LookupGate, LookupGenerator},
+        lookup_table::{LookupTableGate, LookupTableGenerator},
+        multiplication_extension::{MulExtensionGate, MulExtensionGenerator},
+        noop::NoopGate,
+        poseidon::{PoseidonGate, PoseidonGenerator},
+        poseidon_mds::{PoseidonMdsGate, PoseidonMdsGenerator},
+        public_input::PublicInputGate,
+        random_access::{RandomAccessGate, RandomAccessGenerator},
+        reducing::{ReducingGate, ReducingGenerator},
+        reducing_extension::{
+            ReducingExtensionGate, ReducingGenerator as ReducingExtensionGenerator,
+        },
+    },
+    get_gate_tag_impl, get_generator_tag_impl,
+    hash::hash_types::RichField,
+    impl_gate_serializer, impl_generator_serializer,
+    iop::generator::{
+        ConstantGenerator, CopyGenerator, NonzeroTestGenerator, RandomValueGenerator,
+    },
+    plonk::config::{AlgebraicHasher, GenericConfig},
+    read_gate_impl, read_generator_impl,
+    recursion::dummy_circuit::DummyProofGenerator,
+    util::serialization::{GateSerializer, WitnessGeneratorSerializer},
+};
+use plonky2_u32::{
+    gadgets::arithmetic_u32::SplitToU32Generator,
+    gates::{
+        add_many_u32::{U32AddManyGate, U32AddManyGenerator},
+        arithmetic_u32::{U32ArithmeticGate, U32ArithmeticGenerator},
+        comparison::{ComparisonGate, ComparisonGenerator},
+        range_check_u32::{U32RangeCheckGate, U32RangeCheckGenerator},
+        subtraction_u32::{U32SubtractionGate, U32SubtractionGenerator},
+    },
+};
+
+use crate::gates::{
+    BaseSplitGeneratorOptimized, BaseSumGateOptimized, BaseSumGeneratorOptimized, ChGate,
+    ChGenerator, MajGate, MajGenerator, Sha256RoundGate, Sha256RoundGenerator, SigmaGate,
+    SigmaGenerator, Xor3Gate, Xor3Generator,
+};
+use crate::lookup::LimbSplitGenerator;
+use crate::spread::SpreadSplitGenerator;
+
+/// Gate serializer for circuits built with this crate: plonky2's default
+/// gates, the `plonky2_u32` gates and the gates in `crate::gates`.
+///
+/// Tags are positions in the list below, so new gates go at the end to keep
+/// existing serialized circuits readable.
+#[derive(Debug, Default)]
+pub struct Sha256GateSerializer;
+
+impl<F: RichField + Extendable<D>, const D: usize> GateSerializer<F, D> for Sha256GateSerializer {
+    impl_gate_serializer! {
+        Sha256GateSerializer,
+        ArithmeticGate,
+        ArithmeticExtensionGate<D>,
+        BaseSumGate<2>,
+        ConstantGate,
+        CosetInterpolationGate<F, D>,
+        ExponentiationGate<F, D>,
+        LookupGate,
+        LookupTableGate,
+        MulExtensionGate<D>,
+        NoopGate,
+        PoseidonMdsGate<F, D>,
+        PoseidonGate<F, D>,
+        PublicInputGate,
+        RandomAccessGate<F, D>,
+        ReducingExtensionGate<D>,
+        ReducingGate<D>,
+        U32AddManyGate<F, D>,
+        U32ArithmeticGate<F, D>,
+        ComparisonGate<F, D>,
+        U32RangeCheckGate<F, D>,
+        U32SubtractionGate<F, D>,
+        Xor3Gate<F, D>,
+        MajGate<F, D>,
+        ChGate<F, D>,
+        BaseSumGateOptimized<2>,
+        BaseSumGateOptimized<4>,
+        SigmaGate<F, D>,
+        Sha256RoundGate<F, D>
+    }
+}
+
+/// Witness generator serializer matching `Sha256GateSerializer`. Generators
+/// are told apart by `id()`, so every generator listed has an id that does not
+/// depend on its fields.
+#[derive(Debug)]
+pub struct Sha256GeneratorSerializer<C: GenericConfig<D>, const D: usize> {
+    pub _phantom: PhantomData<C>,
+}
+
+impl<C: GenericConfig<D>, const D: usize> Default for Sha256GeneratorSerializer<C, D> {
+    fn default() -> Self {
+        Self {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<F, C, const D: usize> WitnessGeneratorSerializer<F, D> for Sha256GeneratorSerializer<C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    impl_generator_serializer! {
+        Sha256GeneratorSerializer,
+        ArithmeticBaseGenerator<F, D>,
+        ArithmeticExtensionGenerator<F, D>,
+        BaseSplitGenerator<2>,
+        BaseSumGenerator<2>,
+        ConstantGenerator<F>,
+        CopyGenerator,
+        DummyProofGenerator<F, C, D>,
+        EqualityGenerator,
+        ExponentiationGenerator<F, D>,
+        InterpolationGenerator<F, D>,
+        LookupGenerator,
+        LookupTableGenerator,
+        LowHighGenerator,
+        MulExtensionGenerator<F, D>,
+        NonzeroTestGenerator,
+        PoseidonGenerator<F, D>,
+        PoseidonMdsGenerator<D>,
+        QuotientGeneratorExtension<D>,
+        RandomAccessGenerator<F, D>,
+        RandomValueGenerator,
+        ReducingGenerator<D>,
+        ReducingExtensionGenerator<D>,
+        SplitGenerator,
+        WireSplitGenerator,
+        U32AddManyGenerator<F, D>,
+        U32ArithmeticGenerator<F, D>,
+        ComparisonGenerator<F, D>,
+        U32RangeCheckGenerator<F, D>,
+        U32SubtractionGenerator<F, D>,
+        SplitToU32Generator<F, D>,
+        Xor3Generator<F, D>,
+        MajGenerator<F, D>,
+        ChGenerator<F, D>,
+        BaseSumGeneratorOptimized<2>,
+        BaseSplitGeneratorOptimized<2>,
+        LimbSplitGenerator,
+        BaseSumGeneratorOptimized<4>,
+        SpreadSplitGenerator,
+        SigmaGenerator<F, D>,
+        Sha256RoundGenerator<F, D>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::{CircuitConfig, CircuitData, VerifierCircuitData},
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+    use sha2::Digest;
+
+    use crate::circuit::{
+        array_to_bits, digest_from_proof, make_public_circuits, PublicMessage, EXAMPLE_MESSAGE,
+    };
+    use crate::serialization::{Sha256GateSerializer, Sha256GeneratorSerializer};
+
+    #[test]
+    fn test_circuit_data_round_trip() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let gate_serializer = Sha256GateSerializer;
+        let generator_serializer = Sha256GeneratorSerializer::<C, D>::default();
+
+        let msg = &EXAMPLE_MESSAGE[..100];
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let targets =
+            make_public_circuits(&mut builder, (msg.len() * 8) as u64, PublicMessage::None);
+        let data = builder.build::<C>();
+
+        let bytes = data
+            .to_bytes(&gate_serializer, &generator_serializer)
+            .map_err(|_| anyhow::anyhow!("failed to serialize circuit data"))?;
+        let data =
+            CircuitData::<F, C, D>::from_bytes(&bytes, &gate_serializer, &generator_serializer)
+                .map_err(|_| anyhow::anyhow!("failed to deserialize circuit data"))?;
+
+        let verifier_bytes = data
+            .verifier_data()
+            .to_bytes(&gate_serializer)
+            .map_err(|_| anyhow::anyhow!("failed to serialize verifier data"))?;
+        let verifier_data =
+            VerifierCircuitData::<F, C, D>::from_bytes(verifier_bytes, &gate_serializer)
+                .map_err(|_| anyhow::anyhow!("failed to deserialize verifier data"))?;
+
+        // The reloaded prover data still knows how to generate the witness.
+        let mut pw = PartialWitness::new();
+        for (target, bit) in targets.message.iter().zip(array_to_bits(msg)) {
+            pw.set_bool_target(*target, bit)?;
+        }
+        let proof = data.prove(pw)?;
+        assert_eq!(
+            digest_from_proof(&proof),
+            sha2::Sha256::digest(msg).as_slice()
+        );
+        verifier_data.verify(proof)
+    }
+}
@@ -0,0 +1,418 @@
+use plonky2::{
+    field::extension::Extendable, hash::hash_types::RichField, iop::target::BoolTarget,
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_u32::gadgets::arithmetic_u32::CircuitBuilderU32;
+
+use crate::circuit::message_words;
+use crate::gadgets::XorOps;
+use crate::lazy::LazyU64WithBits;
+
+pub const WORD_SIZE: usize = 64;
+
+#[rustfmt::skip]
+pub const H512: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179
+];
+
+#[rustfmt::skip]
+pub const H384: [u64; 8] = [
+    0xcbbb9d5dc1059ed8, 0x629a292a367cd507, 0x9159015a3070dd17, 0x152fecd8f70e5939,
+    0x67332667ffc00b31, 0x8eb44a8768581511, 0xdb0c2e0d64f98fa7, 0x47b5481dbefa4fa4
+];
+
+#[rustfmt::skip]
+pub const H512_256: [u64; 8] = [
+    0x22312194fc2bf72c, 0x9f555fa3c84c64c2, 0x2393b86b6f53b151, 0x963877195940eabd,
+    0x96283ee2a88effe3, 0xbe5e1e2553863992, 0x2b0199fc2c85b8aa, 0x0eb72ddc81c52ca2
+];
+
+/// Constants necessary for SHA-512 family of digests.
+#[rustfmt::skip]
+pub const K512: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817
+];
+
+// define ROTATE(x, y)  (((x)>>(y)) | ((x)<<(64-(y))))
+fn rotate64(y: usize) -> Vec<usize> {
+    let mut res = Vec::new();
+    for i in 64 - y..64 {
+        res.push(i);
+    }
+    for i in 0..64 - y {
+        res.push(i);
+    }
+    res
+}
+
+// x>>y
+// Assume: 0 at index 64
+fn shift64(y: usize) -> Vec<usize> {
+    let mut res = vec![64; y];
+    res.extend(0..64 - y);
+    res
+}
+
+fn xor3_with_permutation<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a_bits: &[BoolTarget],
+    p1: &[usize],
+    p2: &[usize],
+    p3: &[usize],
+) -> Vec<BoolTarget> {
+    let mut res = Vec::new();
+    for i in 0..WORD_SIZE {
+        res.push(builder.add_xor3(a_bits[p1[i]], a_bits[p2[i]], a_bits[p3[i]]));
+    }
+    res
+}
+
+//#define Sigma0(x)    (ROTATE((x),28) ^ ROTATE((x),34) ^ ROTATE((x),39))
+fn big_sigma0_lazy<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: &LazyU64WithBits,
+) -> LazyU64WithBits {
+    let a_bits = a.get_bits(builder);
+    let res_bits = xor3_with_permutation(
+        builder,
+        &a_bits,
+        &rotate64(28),
+        &rotate64(34),
+        &rotate64(39),
+    );
+    LazyU64WithBits::from_bits(res_bits)
+}
+
+//#define Sigma1(x)    (ROTATE((x),14) ^ ROTATE((x),18) ^ ROTATE((x),41))
+fn big_sigma1_lazy<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: &LazyU64WithBits,
+) -> LazyU64WithBits {
+    let a_bits = a.get_bits(builder);
+    let res_bits = xor3_with_permutation(
+        builder,
+        &a_bits,
+        &rotate64(14),
+        &rotate64(18),
+        &rotate64(41),
+    );
+    LazyU64WithBits::from_bits(res_bits)
+}
+
+//#define sigma0(x)    (ROTATE((x), 1) ^ ROTATE((x), 8) ^ ((x)>> 7))
+fn sigma0_lazy<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: &LazyU64WithBits,
+) -> LazyU64WithBits {
+    let mut a_bits = a.get_bits(builder);
+    a_bits.push(builder.constant_bool(false));
+    let res_bits = xor3_with_permutation(builder, &a_bits, &rotate64(1), &rotate64(8), &shift64(7));
+    LazyU64WithBits::from_bits(res_bits)
+}
+
+//#define sigma1(x)    (ROTATE((x),19) ^ ROTATE((x),61) ^ ((x)>> 6))
+fn sigma1_lazy<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: &LazyU64WithBits,
+) -> LazyU64WithBits {
+    let mut a_bits = a.get_bits(builder);
+    a_bits.push(builder.constant_bool(false));
+    let res_bits =
+        xor3_with_permutation(builder, &a_bits, &rotate64(19), &rotate64(61), &shift64(6));
+    LazyU64WithBits::from_bits(res_bits)
+}
+
+fn ch_lazy<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: &LazyU64WithBits,
+    b: &LazyU64WithBits,
+    c: &LazyU64WithBits,
+) -> LazyU64WithBits {
+    let a_bits = a.get_bits(builder);
+    let b_bits = b.get_bits(builder);
+    let c_bits = c.get_bits(builder);
+
+    let mut res_bits = Vec::new();
+    for i in 0..WORD_SIZE {
+        res_bits.push(builder.add_ch(a_bits[i], b_bits[i], c_bits[i]));
+    }
+    LazyU64WithBits::from_bits(res_bits)
+}
+
+fn maj_lazy<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    a: &LazyU64WithBits,
+    b: &LazyU64WithBits,
+    c: &LazyU64WithBits,
+) -> LazyU64WithBits {
+    let a_bits = a.get_bits(builder);
+    let b_bits = b.get_bits(builder);
+    let c_bits = c.get_bits(builder);
+
+    let mut res_bits = Vec::new();
+    for i in 0..WORD_SIZE {
+        res_bits.push(builder.add_maj(a_bits[i], b_bits[i], c_bits[i]));
+    }
+    LazyU64WithBits::from_bits(res_bits)
+}
+
+/// Adds 64-bit words modulo 2^64: the low halves are summed first and their
+/// carry is added into the high halves.
+fn add_many_u64_lazy<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    to_add: &[LazyU64WithBits],
+) -> LazyU64WithBits {
+    let mut his = Vec::new();
+    let mut los = Vec::new();
+    for lazy_u64 in to_add {
+        let [hi, lo] = lazy_u64.get_u32s(builder);
+        his.push(hi);
+        los.push(lo);
+    }
+    let (lo, carry) = builder.add_many_u32(&los[..]);
+    his.push(carry);
+    let (hi, _carry) = builder.add_many_u32(&his[..]);
+    LazyU64WithBits::from_u32s([hi, lo])
+}
+
+fn constant_u64_lazy<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    c: u64,
+) -> LazyU64WithBits {
+    let hi = builder.constant_u32((c >> 32) as u32);
+    let lo = builder.constant_u32(c as u32);
+    LazyU64WithBits::from_u32s([hi, lo])
+}
+
+/// Runs the SHA-512 compression function on the 16-word block `w`, returning
+/// the updated state.
+fn compress_lazy<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    state: &[LazyU64WithBits],
+    w: &[LazyU64WithBits],
+) -> Vec<LazyU64WithBits> {
+    assert_eq!(state.len(), 8);
+    assert_eq!(w.len(), 16);
+    let mut x = w.to_vec();
+
+    let mut a = state[0].clone();
+    let mut b = state[1].clone();
+    let mut c = state[2].clone();
+    let mut d = state[3].clone();
+    let mut e = state[4].clone();
+    let mut f = state[5].clone();
+    let mut g = state[6].clone();
+    let mut h = state[7].clone();
+
+    for (i, k) in K512.iter().enumerate() {
+        if i >= 16 {
+            let s0 = sigma0_lazy(builder, &x[(i + 1) & 0x0f]);
+            let s1 = sigma1_lazy(builder, &x[(i + 14) & 0x0f]);
+            x[i & 0xf] = add_many_u64_lazy(
+                builder,
+                &[x[i & 0xf].clone(), s0, s1, x[(i + 9) & 0xf].clone()],
+            );
+        }
+
+        let big_sigma1_e = big_sigma1_lazy(builder, &e);
+        let ch_e_f_g = ch_lazy(builder, &e, &f, &g);
+        let k512 = constant_u64_lazy(builder, *k);
+        let t1 = add_many_u64_lazy(
+            builder,
+            &[h, big_sigma1_e, ch_e_f_g, k512, x[i & 0xf].clone()],
+        );
+
+        let big_sigma0_a = big_sigma0_lazy(builder, &a);
+        let maj_a_b_c = maj_lazy(builder, &a, &b, &c);
+        let t2 = add_many_u64_lazy(builder, &[big_sigma0_a, maj_a_b_c]);
+
+        h = g;
+        g = f;
+        f = e;
+        e = add_many_u64_lazy(builder, &[d, t1.clone()]);
+        d = c;
+        c = b;
+        b = a;
+        a = add_many_u64_lazy(builder, &[t1, t2]);
+    }
+
+    let z = [a, b, c, d, e, f, g, h];
+    state
+        .iter()
+        .zip(z)
+        .map(|(s, z)| add_many_u64_lazy(builder, &[s.clone(), z]))
+        .collect()
+}
+
+pub struct Sha512Targets {
+    /// Message bits, constrained to be boolean by `message_words`.
+    pub message: Vec<BoolTarget>,
+    pub digest: Vec<BoolTarget>,
+}
+
+// padded_msg_len = block_count x 1024 bits
+// Size: msg_len_in_bits (L) |  p bits   | 128 bits
+// Bits:      msg            | 100...000 |    L
+fn make_sha512_family_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+    iv: &[u64; 8],
+    digest_words: usize,
+) -> Sha512Targets {
+    let mut message = Vec::new();
+    let block_count = (msg_len_in_bits + 129).div_ceil(1024);
+    let padded_msg_len = 1024 * block_count;
+    let p = padded_msg_len - 128 - msg_len_in_bits;
+
+    for _ in 0..msg_len_in_bits {
+        message.push(builder.add_virtual_bool_target_unsafe());
+    }
+    message.push(builder.constant_bool(true));
+    for _ in 0..p - 1 {
+        message.push(builder.constant_bool(false));
+    }
+    for i in 0..128 {
+        let b = (msg_len_in_bits as u128 >> (127 - i)) & 1;
+        message.push(builder.constant_bool(b == 1));
+    }
+    let words = message_words(builder, &message);
+
+    let mut state = Vec::new();
+    for c in iv {
+        state.push(constant_u64_lazy(builder, *c));
+    }
+    for block in words.chunks(32) {
+        let w: Vec<_> = block
+            .chunks(2)
+            .map(|hi_lo| LazyU64WithBits::from_u32s([hi_lo[0], hi_lo[1]]))
+            .collect();
+        state = compress_lazy(builder, &state, &w);
+    }
+
+    // Only decompose to bits for the final digest output
+    let mut digest = Vec::new();
+    for word in &state[..digest_words] {
+        digest.extend(word.get_bits(builder));
+    }
+
+    Sha512Targets { message, digest }
+}
+
+pub fn make_sha512_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+) -> Sha512Targets {
+    make_sha512_family_circuits(builder, msg_len_in_bits, &H512, 8)
+}
+
+/// SHA-384: SHA-512 with its own IV, truncated to 384 bits.
+pub fn make_sha384_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+) -> Sha512Targets {
+    make_sha512_family_circuits(builder, msg_len_in_bits, &H384, 6)
+}
+
+/// SHA-512/256: SHA-512 with its own IV, truncated to 256 bits.
+pub fn make_sha512_256_circuits<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    msg_len_in_bits: u64,
+) -> Sha512Targets {
+    make_sha512_family_circuits(builder, msg_len_in_bits, &H512_256, 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+    use sha2::Digest;
+
+    use crate::circuit::{array_to_bits, EXAMPLE_MESSAGE};
+    use crate::sha512::{
+        make_sha384_circuits, make_sha512_256_circuits, make_sha512_circuits, Sha512Targets,
+    };
+
+    fn check_sha512_family(
+        msg: &[u8],
+        expected: &[u8],
+        make: fn(
+            &mut CircuitBuilder<<PoseidonGoldilocksConfig as GenericConfig<2>>::F, 2>,
+            u64,
+        ) -> Sha512Targets,
+    ) -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let targets = make(&mut builder, (msg.len() * 8) as u64);
+        assert_eq!(targets.digest.len(), expected.len() * 8);
+
+        let mut pw = PartialWitness::new();
+        for (target, bit) in targets.message.iter().zip(array_to_bits(msg)) {
+            pw.set_bool_target(*target, bit)?;
+        }
+
+        let expected_digest_bits = array_to_bits(expected);
+        for (i, expected_digest_bit) in expected_digest_bits.iter().enumerate() {
+            if *expected_digest_bit {
+                builder.assert_one(targets.digest[i].target);
+            } else {
+                builder.assert_zero(targets.digest[i].target);
+            }
+        }
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_sha512_circuit() -> anyhow::Result<()> {
+        // Two blocks.
+        let msg = &EXAMPLE_MESSAGE[..200];
+        check_sha512_family(msg, &sha2::Sha512::digest(msg), make_sha512_circuits)
+    }
+
+    #[test]
+    fn test_sha384_circuit() -> anyhow::Result<()> {
+        let msg = b"abc";
+        check_sha512_family(msg, &sha2::Sha384::digest(msg), make_sha384_circuits)
+    }
+
+    #[test]
+    fn test_sha512_256_circuit() -> anyhow::Result<()> {
+        let msg = b"abc";
+        check_sha512_family(
+            msg,
+            &sha2::Sha512_256::digest(msg),
+            make_sha512_256_circuits,
+        )
+    }
+}
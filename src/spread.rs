@@ -0,0 +1,384 @@
+use anyhow::Result;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use plonky2::{
+    field::extension::Extendable,
+    hash::hash_types::RichField,
+    iop::{
+        generator::{GeneratedValues, SimpleGenerator},
+        target::{BoolTarget, Target},
+        witness::{PartitionWitness, Witness},
+    },
+    plonk::{circuit_builder::CircuitBuilder, circuit_data::CommonCircuitData},
+    util::serialization::{Buffer, IoResult, Read, Write},
+};
+use plonky2_u32::gadgets::arithmetic_u32::U32Target;
+
+use crate::circuit::Sha256RoundFunctions;
+use crate::gadgets::XorOps;
+use crate::gates::BaseSumGateOptimized;
+use crate::lazy::LazyU32WithBits;
+use crate::lookup::Shift;
+
+/// `spread(0xffff)`: the spread form of an all-ones half-word.
+const SPREAD_ONES: u64 = 0x5555_5555;
+
+/// A word for `SpreadRoundFunctions`: a `LazyU32WithBits` plus the spread
+/// forms of its 16-bit halves, computed on first use and shared by clones.
+#[derive(Clone, Debug)]
+pub struct SpreadWord {
+    word: LazyU32WithBits,
+    /// Spread forms of the low and high halves.
+    spread: Rc<RefCell<Option<[Target; 2]>>>,
+}
+
+impl SpreadWord {
+    fn new(word: LazyU32WithBits) -> Self {
+        Self {
+            word,
+            spread: Rc::new(RefCell::new(None)),
+        }
+    }
+}
+
+/// One 16-bit half of a split spread sum.
+struct SpreadHalf {
+    value: Target,
+    /// Little-endian bits.
+    bits: Vec<BoolTarget>,
+    spread: Target,
+}
+
+/// The spread backend. The spread form of `x` puts bit `i` at bit `2i`, i.e.
+/// writes `x` in base 4 with digits in `{0, 1}`, so adding the spread forms of
+/// up to three words counts the ones at every bit position. Each base-4 digit
+/// `d` of such a sum is then `even + 2 * odd`, where `even` is the XOR of the
+/// bits and `odd` their majority (or AND, for two words).
+///
+/// Spread forms are computed by wiring boolean limbs into a
+/// `BaseSumGateOptimized<4>`. A sum of three spread words can exceed the
+/// Goldilocks order, so everything is done on 16-bit halves.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SpreadRoundFunctions;
+
+impl SpreadRoundFunctions {
+    /// Returns `sum bits[i] * 4^i`. The bits must already be boolean; the gate
+    /// only checks that they are base-4 digits.
+    fn le_spread_sum<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        bits: &[BoolTarget],
+    ) -> Target {
+        assert_eq!(bits.len(), 16);
+        let gate_type = BaseSumGateOptimized::<4>::new_from_config(&builder.config, bits.len());
+        let (row, i) = builder.find_slot(gate_type, &[], &[]);
+        for (bit, wire) in bits.iter().zip(gate_type.limbs(i)) {
+            builder.connect(bit.target, Target::wire(row, wire));
+        }
+        let offset = i * (gate_type.num_limbs + 1);
+        Target::wire(row, BaseSumGateOptimized::<4>::WIRE_SUM + offset)
+    }
+
+    /// Splits a sum of spread half-words into its even and odd bits,
+    /// constraining `s = spread(even) + 2 * spread(odd)`.
+    fn split_spread_sum<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        s: Target,
+    ) -> (SpreadHalf, SpreadHalf) {
+        let even: Vec<_> = (0..16)
+            .map(|_| builder.add_virtual_bool_target_unsafe())
+            .collect();
+        let odd: Vec<_> = (0..16)
+            .map(|_| builder.add_virtual_bool_target_unsafe())
+            .collect();
+        builder.add_simple_generator(SpreadSplitGenerator {
+            sum: s,
+            even: even.iter().map(|b| b.target).collect(),
+            odd: odd.iter().map(|b| b.target).collect(),
+        });
+
+        // `le_sum_optimized` makes the bits boolean, so the digits `even[i] +
+        // 2 * odd[i]` are in `{0, 1, 2, 3}` and the base-4 representation of
+        // `s` pins them down.
+        let [even, odd] = [even, odd].map(|bits| {
+            let value = builder.le_sum_optimized(bits.iter());
+            let spread = Self::le_spread_sum(builder, &bits);
+            SpreadHalf {
+                value,
+                bits,
+                spread,
+            }
+        });
+        let recomposed = builder.mul_const_add(F::TWO, odd.spread, even.spread);
+        builder.connect(recomposed, s);
+        (even, odd)
+    }
+
+    fn spread_halves<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        x: &SpreadWord,
+    ) -> [Target; 2] {
+        if let Some(spread) = *x.spread.borrow() {
+            return spread;
+        }
+        let mut bits = x.word.get_bits(builder);
+        bits.reverse();
+        let spread = [
+            Self::le_spread_sum(builder, &bits[..16]),
+            Self::le_spread_sum(builder, &bits[16..]),
+        ];
+        *x.spread.borrow_mut() = Some(spread);
+        spread
+    }
+
+    /// XOR of three rotations or shifts of `x`.
+    fn xor3_shifts<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        x: &SpreadWord,
+        shifts: [Shift; 3],
+    ) -> SpreadWord {
+        let mut bits = x.word.get_bits(builder);
+        bits.reverse();
+        let zero = builder._false();
+        let shifted = shifts.map(|shift| shift.apply(&bits, zero));
+
+        let mut values = Vec::new();
+        let mut out_bits = Vec::new();
+        let mut spread = [zero.target; 2];
+        for (h, spread) in spread.iter_mut().enumerate() {
+            let halves: Vec<_> = shifted
+                .iter()
+                .map(|bits| Self::le_spread_sum(builder, &bits[16 * h..16 * (h + 1)]))
+                .collect();
+            let s = builder.add_many(halves);
+            let (even, _odd) = Self::split_spread_sum(builder, s);
+            values.push(even.value);
+            out_bits.extend(even.bits);
+            *spread = even.spread;
+        }
+
+        let value = builder.mul_const_add(F::from_canonical_u64(1 << 16), values[1], values[0]);
+        out_bits.reverse();
+        let out = SpreadWord::new(LazyU32WithBits::from_u32_and_bits(
+            U32Target(value),
+            out_bits,
+        ));
+        *out.spread.borrow_mut() = Some(spread);
+        out
+    }
+
+    /// Joins the odd bits of the spread sums `[lo, hi]` into a word.
+    fn odd_word<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        sums: [Target; 2],
+    ) -> Target {
+        let [lo, hi] = sums.map(|s| Self::split_spread_sum(builder, s).1.value);
+        builder.mul_const_add(F::from_canonical_u64(1 << 16), hi, lo)
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Sha256RoundFunctions<F, D>
+    for SpreadRoundFunctions
+{
+    type Word = SpreadWord;
+
+    fn u32_to_word(&mut self, _builder: &mut CircuitBuilder<F, D>, x: U32Target) -> Self::Word {
+        SpreadWord::new(LazyU32WithBits::from_u32(x))
+    }
+
+    fn word_to_u32(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> U32Target {
+        x.word.get_u32(builder)
+    }
+
+    fn big_sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        Self::xor3_shifts(
+            builder,
+            x,
+            [Shift::Rotr(2), Shift::Rotr(13), Shift::Rotr(22)],
+        )
+    }
+
+    fn big_sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        Self::xor3_shifts(
+            builder,
+            x,
+            [Shift::Rotr(6), Shift::Rotr(11), Shift::Rotr(25)],
+        )
+    }
+
+    fn sigma0(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        Self::xor3_shifts(builder, x, [Shift::Rotr(7), Shift::Rotr(18), Shift::Shr(3)])
+    }
+
+    fn sigma1(&mut self, builder: &mut CircuitBuilder<F, D>, x: &Self::Word) -> Self::Word {
+        Self::xor3_shifts(
+            builder,
+            x,
+            [Shift::Rotr(17), Shift::Rotr(19), Shift::Shr(10)],
+        )
+    }
+
+    // ch = (e & f) + (!e & g): the two terms never overlap, and each is the
+    // odd bits of a two-word spread sum, with spread(!e) = SPREAD_ONES - spread(e).
+    fn ch(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        e: &Self::Word,
+        f: &Self::Word,
+        g: &Self::Word,
+    ) -> Self::Word {
+        let e = Self::spread_halves(builder, e);
+        let f = Self::spread_halves(builder, f);
+        let g = Self::spread_halves(builder, g);
+        let e_plus_f = [0, 1].map(|h| builder.add(e[h], f[h]));
+        let not_e_plus_g = [0, 1].map(|h| {
+            let g_minus_e = builder.sub(g[h], e[h]);
+            builder.add_const(g_minus_e, F::from_canonical_u64(SPREAD_ONES))
+        });
+        let e_and_f = Self::odd_word(builder, e_plus_f);
+        let not_e_and_g = Self::odd_word(builder, not_e_plus_g);
+        let value = builder.add(e_and_f, not_e_and_g);
+        SpreadWord::new(LazyU32WithBits::from_u32(U32Target(value)))
+    }
+
+    fn maj(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        a: &Self::Word,
+        b: &Self::Word,
+        c: &Self::Word,
+    ) -> Self::Word {
+        let a = Self::spread_halves(builder, a);
+        let b = Self::spread_halves(builder, b);
+        let c = Self::spread_halves(builder, c);
+        let sums = [0, 1].map(|h| builder.add_many([a[h], b[h], c[h]]));
+        let value = Self::odd_word(builder, sums);
+        SpreadWord::new(LazyU32WithBits::from_u32(U32Target(value)))
+    }
+}
+
+/// Splits a sum of spread half-words into its even and odd bits for
+/// `SpreadRoundFunctions`.
+#[derive(Debug, Default)]
+pub struct SpreadSplitGenerator {
+    sum: Target,
+    even: Vec<Target>,
+    odd: Vec<Target>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for SpreadSplitGenerator {
+    fn id(&self) -> String {
+        "SpreadSplitGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        vec![self.sum]
+    }
+
+    fn run_once(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+    ) -> Result<()> {
+        let sum = witness.get_target(self.sum).to_canonical_u64();
+        for (i, (&even, &odd)) in self.even.iter().zip(&self.odd).enumerate() {
+            let digit = (sum >> (2 * i)) & 3;
+            out_buffer.set_target(even, F::from_canonical_u64(digit & 1))?;
+            out_buffer.set_target(odd, F::from_canonical_u64(digit >> 1))?;
+        }
+        Ok(())
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_target(self.sum)?;
+        dst.write_target_vec(&self.even)?;
+        dst.write_target_vec(&self.odd)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let sum = src.read_target()?;
+        let even = src.read_target_vec()?;
+        let odd = src.read_target_vec()?;
+        Ok(Self { sum, even, odd })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::CircuitConfig,
+            config::{GenericConfig, PoseidonGoldilocksConfig},
+        },
+    };
+    use plonky2_u32::gadgets::arithmetic_u32::CircuitBuilderU32;
+    use sha2::Digest;
+
+    use crate::circuit::{
+        array_to_bits, make_circuits_u32_with_backend, Sha256Backend, EXAMPLE_MESSAGE,
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_spread_backend() -> anyhow::Result<()> {
+        // Two blocks, with all-ones bytes to hit the largest digit sums.
+        let mut msg = EXAMPLE_MESSAGE[..100].to_vec();
+        msg[..8].fill(0xff);
+        let digest = sha2::Sha256::digest(&msg);
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let targets = make_circuits_u32_with_backend(
+            &mut builder,
+            (msg.len() * 8) as u64,
+            Sha256Backend::Spread,
+        );
+        for (i, word) in targets.digest.iter().enumerate() {
+            let expected = u32::from_be_bytes(digest[4 * i..4 * i + 4].try_into().unwrap());
+            let expected = builder.constant_u32(expected);
+            builder.connect_u32(*word, expected);
+        }
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        for (target, bit) in targets.message.iter().zip(array_to_bits(&msg)) {
+            pw.set_bool_target(*target, bit)?;
+        }
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    /// Rows one compression adds with each backend, measured as the gate
+    /// count difference between a two-block and a one-block message.
+    #[test]
+    fn test_rows_per_block() {
+        let num_gates = |backend, msg_len_in_bits| {
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            make_circuits_u32_with_backend(&mut builder, msg_len_in_bits, backend);
+            builder.num_gates()
+        };
+        let rows_per_block = [
+            Sha256Backend::Bits,
+            Sha256Backend::Lookup,
+            Sha256Backend::Spread,
+        ]
+        .map(|backend| {
+            let one_block = num_gates(backend, 256);
+            let two_blocks = num_gates(backend, 768);
+            assert!(two_blocks > one_block, "{backend:?}");
+            two_blocks - one_block
+        });
+        let [bits, _, spread] = rows_per_block;
+
+        // A spread σ takes ten 16-limb base-4 and four 16-limb base-2
+        // decompositions, four to a row with 80 routed wires, where the bit
+        // backend packs 20 bits of Ch or Maj into a row and a whole σ into
+        // less than two. Spread only wins on the number of custom gates.
+        assert!(spread > bits, "{rows_per_block:?}");
+    }
+}